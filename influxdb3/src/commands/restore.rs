@@ -0,0 +1,42 @@
+//! Restore a node from a backup taken with the `POST /api/v3/configure/backup` API
+
+use std::sync::Arc;
+
+use influxdb3_clap_blocks::object_store::ObjectStoreConfig;
+use influxdb3_write::backup;
+use object_store::ObjectStore;
+
+#[derive(Debug, clap::Parser)]
+pub struct Config {
+    /// object store options
+    #[clap(flatten)]
+    object_store_config: ObjectStoreConfig,
+
+    /// The node identifier of the node to restore; this must be the same as the node that the
+    /// backup was taken from
+    #[clap(long = "node-id", env = "INFLUXDB3_NODE_IDENTIFIER_PREFIX", action)]
+    node_identifier_prefix: String,
+
+    /// The sequence number of the backup to restore, the most recent backup is restored if not
+    /// provided
+    #[clap(long = "backup", action)]
+    backup_sequence_number: Option<u64>,
+}
+
+pub async fn command(config: Config) -> Result<(), anyhow::Error> {
+    let object_store: Arc<dyn ObjectStore> = config.object_store_config.make_object_store()?;
+    let manifest = backup::restore(
+        object_store,
+        &config.node_identifier_prefix,
+        config.backup_sequence_number,
+    )
+    .await?;
+    println!(
+        "Restored backup {} of node {} ({} files, up to WAL file {})",
+        manifest.backup_sequence_number,
+        manifest.node_id,
+        manifest.files.len(),
+        manifest.wal_high_water_mark,
+    );
+    Ok(())
+}
@@ -63,6 +63,9 @@ use trogging::cli::LoggingConfig;
 
 use crate::commands::common::warn_use_of_deprecated_env_vars;
 
+pub mod config_file;
+use config_file::{ConfigFile, ConfigFileReloader, LogLayerHandle};
+
 /// The default name of the influxdb data directory
 #[allow(dead_code)]
 pub const DEFAULT_DATA_DIRECTORY_NAME: &str = ".influxdb3";
@@ -362,6 +365,27 @@ pub struct Config {
     /// smaller time ranges if possible in a query.
     #[clap(long = "query-file-limit", env = "INFLUXDB3_QUERY_FILE_LIMIT", action)]
     pub query_file_limit: Option<usize>,
+
+    /// Enable the `/debug/pprof/profile` and `/debug/pprof/heap` endpoints on the HTTP API.
+    /// These can be used to collect a CPU profile, or a summary of heap usage, from a running
+    /// server without restarting it with a special build.
+    #[clap(
+        long = "enable-debug-endpoints",
+        env = "INFLUXDB3_ENABLE_DEBUG_ENDPOINTS",
+        default_value_t = false,
+        action
+    )]
+    pub enable_debug_endpoints: bool,
+
+    /// A TOML file of settings for the options of this command, keyed by their long names, e.g.,
+    /// `query-file-limit = 500`. Options given on the command line or in the environment take
+    /// precedence over the file.
+    ///
+    /// The file is reloaded on SIGHUP, or a request to `POST /api/v3/configure/reload`; the
+    /// `log-filter`, `max-http-request-size`, and `query-file-limit` settings are applied to the
+    /// running server, and changes to any other settings are reported as requiring a restart.
+    #[clap(long = "config-file", env = "INFLUXDB3_CONFIG_FILE", action)]
+    pub config_file: Option<PathBuf>,
 }
 
 /// Specified size of the Parquet cache in megabytes (MB)
@@ -428,7 +452,11 @@ fn ensure_directory_exists(p: &Path) {
     }
 }
 
-pub async fn command(config: Config) -> Result<()> {
+pub async fn command(
+    config: Config,
+    config_file: Option<ConfigFile>,
+    log_layer: LogLayerHandle,
+) -> Result<()> {
     let startup_timer = Instant::now();
     let num_cpus = num_cpus::get();
     let build_malloc_conf = build_malloc_conf();
@@ -592,6 +620,7 @@ pub async fn command(config: Config) -> Result<()> {
     )
     .await;
 
+    let query_file_limit = write_buffer_impl.query_file_limit_handle();
     let write_buffer: Arc<dyn WriteBuffer> = write_buffer_impl;
 
     let common_state = CommonServerState::new(
@@ -628,6 +657,7 @@ pub async fn command(config: Config) -> Result<()> {
 
     let builder = ServerBuilder::new(common_state)
         .max_request_size(config.max_http_request_size)
+        .debug_endpoints_enabled(config.enable_debug_endpoints)
         .write_buffer(write_buffer)
         .query_executor(query_executor)
         .time_provider(time_provider)
@@ -635,6 +665,22 @@ pub async fn command(config: Config) -> Result<()> {
         .tcp_listener(listener)
         .processing_engine(processing_engine);
 
+    let builder = if let Some(config_file) = config_file {
+        info!(path = ?config_file.path(), "loaded config file");
+        let reloader = Arc::new(ConfigFileReloader::new(
+            config_file,
+            config.logging_config,
+            log_layer,
+            builder.max_request_size_handle(),
+            query_file_limit,
+        ));
+        #[cfg(unix)]
+        reload_config_on_sighup(Arc::clone(&reloader));
+        builder.config_reloader(reloader)
+    } else {
+        builder
+    };
+
     let server = if let Some(token) = config.bearer_token.map(hex::decode).transpose()? {
         builder
             .authorizer(Arc::new(AllOrNothingAuthorizer::new(token)))
@@ -648,6 +694,29 @@ pub async fn command(config: Config) -> Result<()> {
     Ok(())
 }
 
+/// Reload the config file whenever the process receives a SIGHUP
+#[cfg(unix)]
+fn reload_config_on_sighup(reloader: Arc<ConfigFileReloader>) {
+    use influxdb3_server::reload::ConfigReloader;
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(error) => {
+            warn!(%error, "unable to listen for SIGHUP, the config file is only reloaded by the API");
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            info!("received SIGHUP, reloading config file");
+            if let Err(error) = reloader.reload().await {
+                error!(%error, "unable to reload config file");
+            }
+        }
+    });
+}
+
 pub(crate) fn setup_processing_engine_env_manager(
     config: &ProcessingEngineConfig,
 ) -> ProcessingEngineEnvironmentManager {
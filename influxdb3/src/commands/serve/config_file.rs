@@ -0,0 +1,526 @@
+//! Loading of the `influxdb3 serve` options from a TOML config file, and reloading of that file
+//! while the server is running.
+//!
+//! The keys in the file are the long names of the command line options, e.g.,
+//!
+//! ```toml
+//! node-id = "node0"
+//! object-store = "file"
+//! data-dir = "/var/lib/influxdb3"
+//! log-filter = "info,influxdb3_write=debug"
+//! query-file-limit = 500
+//! ```
+//!
+//! On startup, the settings in the file are applied in the same way as those in a `.env` file:
+//! each one sets the environment variable of its option, unless the option is already given on
+//! the command line or in the environment, which take precedence over the file.
+//!
+//! When the file is reloaded, by a `SIGHUP` or the `POST /api/v3/configure/reload` API, the
+//! changed settings that are in [`LIVE_SETTINGS`] are applied to the running server, and any
+//! other changed settings are reported as requiring a restart.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use async_trait::async_trait;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory};
+use influxdb3_server::reload::{self, ConfigReloader};
+use influxdb3_types::http::{ConfigReloadResponse, ConfigSettingChange};
+use influxdb3_write::write_buffer::DEFAULT_QUERY_FILE_LIMIT;
+use observability_deps::tracing::{error, info};
+use parking_lot::Mutex;
+use thiserror::Error;
+use trogging::cli::{LoggingConfig, LoggingConfigBuilderExt};
+use trogging::tracing_subscriber::{EnvFilter, Layer, Registry, reload::Handle};
+
+use crate::DEFAULT_LOG_FILTER;
+
+/// The settings that are applied to the running server when they are changed in the config file
+pub(crate) const LIVE_SETTINGS: &[&str] =
+    &["log-filter", "max-http-request-size", "query-file-limit"];
+
+/// The option of `influxdb3 serve` that names the config file, which can not be set in the file
+const CONFIG_FILE_OPTION: &str = "config-file";
+
+/// The layer that writes the server's logs, as built by trogging
+pub(crate) type LogLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// A handle to replace the layer that writes the server's logs
+pub(crate) type LogLayerHandle = Handle<LogLayer, Registry>;
+
+/// Build the layer that writes the server's logs from the logging config, with the given
+/// `log_filter` in place of the one in the config, if any
+pub(crate) fn build_log_layer(
+    config: &LoggingConfig,
+    log_filter: Option<String>,
+) -> Result<LogLayer, trogging::Error> {
+    let mut builder = trogging::Builder::new()
+        .with_default_log_filter(DEFAULT_LOG_FILTER)
+        .with_logging_config(config);
+    if log_filter.is_some() {
+        builder = builder.with_log_filter(&log_filter);
+    }
+    Ok(Box::new(builder.build::<Registry>()?))
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unable to read config file {path:?}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("unable to parse config file {path:?}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error("unknown setting '{0}' in config file")]
+    UnknownSetting(String),
+
+    #[error("invalid value for setting '{setting}': {reason}")]
+    InvalidValue { setting: String, reason: String },
+}
+
+/// The settings in a config file
+#[derive(Debug, Clone)]
+pub struct ConfigFile {
+    path: PathBuf,
+    settings: BTreeMap<String, String>,
+    /// The options that are given on the command line or in the environment, which take
+    /// precedence over the file
+    overridden: BTreeSet<String>,
+}
+
+impl ConfigFile {
+    /// Load the config file on startup, before the async runtime is started
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+        let contents = std::fs::read_to_string(&path).map_err(|source| Error::Read {
+            path: path.clone(),
+            source,
+        })?;
+        let settings = parse_settings(&path, &contents)?;
+        Ok(Self {
+            path,
+            settings,
+            overridden: BTreeSet::new(),
+        })
+    }
+
+    /// Set the environment variable of the option for each setting in the file, unless the
+    /// option is given on the command line or in the environment already, according to the
+    /// `matches` of the serve command that were parsed before the file was loaded
+    ///
+    /// # Safety
+    ///
+    /// This must be called before any other threads are started, see [`std::env::set_var`].
+    pub unsafe fn apply_to_env(&mut self, matches: &ArgMatches) {
+        self.overridden = given_options(matches);
+        let options = serve_options();
+        for (name, value) in &self.settings {
+            if !self.overridden.contains(name) {
+                // SAFETY: the caller guarantees that no other threads are running
+                unsafe { std::env::set_var(&options[name].env, value) };
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Read the settings in the config file while the server is running
+async fn read_settings(path: &Path) -> Result<BTreeMap<String, String>, Error> {
+    let contents = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|source| Error::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    parse_settings(path, &contents)
+}
+
+fn parse_settings(path: &Path, contents: &str) -> Result<BTreeMap<String, String>, Error> {
+    let table = contents
+        .parse::<toml::Table>()
+        .map_err(|source| Error::Parse {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    let options = serve_options();
+    let mut settings = BTreeMap::new();
+    for (name, value) in table {
+        if !options.contains_key(&name) {
+            return Err(Error::UnknownSetting(name));
+        }
+        let value = match value {
+            toml::Value::String(s) => s,
+            toml::Value::Integer(i) => i.to_string(),
+            toml::Value::Float(f) => f.to_string(),
+            toml::Value::Boolean(b) => b.to_string(),
+            other => {
+                return Err(Error::InvalidValue {
+                    reason: format!(
+                        "expected a string, number, or boolean, got {}",
+                        other.type_str()
+                    ),
+                    setting: name,
+                });
+            }
+        };
+        settings.insert(name, value);
+    }
+    Ok(settings)
+}
+
+/// An option of `influxdb3 serve` that can be set in a config file
+#[derive(Debug)]
+struct ServeOption {
+    /// The id of the option's argument in the parsed command line
+    id: String,
+    env: String,
+}
+
+/// The options of `influxdb3 serve` that can be set in a config file, by their long name
+fn serve_options() -> HashMap<String, ServeOption> {
+    super::Config::command()
+        .get_arguments()
+        .filter_map(|arg| {
+            let name = arg.get_long()?;
+            let option = ServeOption {
+                id: arg.get_id().to_string(),
+                env: arg.get_env()?.to_str()?.to_string(),
+            };
+            (name != CONFIG_FILE_OPTION).then(|| (name.to_string(), option))
+        })
+        .collect()
+}
+
+/// The options of `influxdb3 serve` that are given on the command line or in the environment,
+/// according to the `matches` of the command
+fn given_options(matches: &ArgMatches) -> BTreeSet<String> {
+    serve_options()
+        .into_iter()
+        .filter(|(_, option)| {
+            matches!(
+                matches.value_source(&option.id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        })
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// The default value of an option of `influxdb3 serve`
+fn option_default(name: &str) -> Option<String> {
+    super::Config::command()
+        .get_arguments()
+        .find(|arg| arg.get_long() == Some(name))
+        .and_then(|arg| arg.get_default_values().first())
+        .and_then(|value| value.to_str())
+        .map(str::to_string)
+}
+
+/// The settings that differ between two versions of a config file
+fn changes(
+    old: &BTreeMap<String, String>,
+    new: &BTreeMap<String, String>,
+) -> Vec<ConfigSettingChange> {
+    old.keys()
+        .chain(new.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|name| old.get(*name) != new.get(*name))
+        .map(|name| ConfigSettingChange {
+            setting: name.clone(),
+            old: old.get(name).cloned(),
+            new: new.get(name).cloned(),
+        })
+        .collect()
+}
+
+/// A change to a setting in [`LIVE_SETTINGS`], parsed from the config file
+#[derive(Debug)]
+enum LiveChange {
+    LogFilter(String),
+    MaxHttpRequestSize(usize),
+    QueryFileLimit(Option<usize>),
+}
+
+impl LiveChange {
+    fn parse(change: &ConfigSettingChange) -> Result<Self, Error> {
+        let value = change
+            .new
+            .clone()
+            .or_else(|| option_default(&change.setting));
+        let invalid = |reason: String| Error::InvalidValue {
+            setting: change.setting.clone(),
+            reason,
+        };
+        match change.setting.as_str() {
+            "log-filter" => {
+                let filter = value.unwrap_or_else(|| DEFAULT_LOG_FILTER.to_string());
+                match EnvFilter::try_new(&filter) {
+                    Ok(_) => Ok(Self::LogFilter(filter)),
+                    Err(e) => Err(invalid(e.to_string())),
+                }
+            }
+            "max-http-request-size" => value
+                .unwrap_or_default()
+                .parse()
+                .map(Self::MaxHttpRequestSize)
+                .map_err(|e| invalid(e.to_string())),
+            "query-file-limit" => value
+                .map(|v| v.parse())
+                .transpose()
+                .map(Self::QueryFileLimit)
+                .map_err(|e| invalid(e.to_string())),
+            setting => unreachable!("setting '{setting}' is not a live setting"),
+        }
+    }
+}
+
+/// Reloads the config file that the server was started with, see the [module docs][self]
+pub struct ConfigFileReloader {
+    startup: ConfigFile,
+    /// The settings from the config file that the running server uses
+    running: Mutex<BTreeMap<String, String>>,
+    /// The logging config the server was started with, which the log layer is rebuilt from
+    logging_config: LoggingConfig,
+    log_layer: LogLayerHandle,
+    max_request_size: Arc<AtomicUsize>,
+    query_file_limit: Arc<AtomicUsize>,
+}
+
+impl std::fmt::Debug for ConfigFileReloader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConfigFileReloader")
+            .field("startup", &self.startup)
+            .field("running", &self.running)
+            .field("logging_config", &self.logging_config)
+            .field("max_request_size", &self.max_request_size)
+            .field("query_file_limit", &self.query_file_limit)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ConfigFileReloader {
+    pub(crate) fn new(
+        startup: ConfigFile,
+        logging_config: LoggingConfig,
+        log_layer: LogLayerHandle,
+        max_request_size: Arc<AtomicUsize>,
+        query_file_limit: Arc<AtomicUsize>,
+    ) -> Self {
+        Self {
+            running: Mutex::new(startup.settings.clone()),
+            startup,
+            logging_config,
+            log_layer,
+            max_request_size,
+            query_file_limit,
+        }
+    }
+
+    /// Check if a setting is overridden on the command line or in the environment; `-v` flags
+    /// override the log filter
+    fn is_overridden(&self, setting: &str) -> bool {
+        self.startup.overridden.contains(setting)
+            || (setting == "log-filter" && self.logging_config.log_verbose_count > 0)
+    }
+
+    fn apply(&self, change: LiveChange) {
+        match change {
+            LiveChange::LogFilter(filter) => {
+                let reloaded = build_log_layer(&self.logging_config, Some(filter))
+                    .map_err(|e| e.to_string())
+                    .and_then(|layer| self.log_layer.reload(layer).map_err(|e| e.to_string()));
+                if let Err(error) = reloaded {
+                    error!(%error, "unable to change the log filter");
+                }
+            }
+            LiveChange::MaxHttpRequestSize(size) => {
+                self.max_request_size.store(size, Ordering::Relaxed)
+            }
+            LiveChange::QueryFileLimit(limit) => self
+                .query_file_limit
+                .store(limit.unwrap_or(DEFAULT_QUERY_FILE_LIMIT), Ordering::Relaxed),
+        }
+    }
+}
+
+#[async_trait]
+impl ConfigReloader for ConfigFileReloader {
+    async fn reload(&self) -> Result<ConfigReloadResponse, reload::Error> {
+        let settings = read_settings(self.startup.path())
+            .await
+            .map_err(|e| reload::Error::InvalidConfig(e.to_string()))?;
+
+        let mut running = self.running.lock();
+        let mut response = ConfigReloadResponse::default();
+        let mut live_changes = vec![];
+        for change in changes(&running, &settings) {
+            if self.is_overridden(&change.setting) {
+                response.overridden.push(change);
+            } else if LIVE_SETTINGS.contains(&change.setting.as_str()) {
+                live_changes.push(
+                    LiveChange::parse(&change)
+                        .map_err(|e| reload::Error::InvalidConfig(e.to_string()))?,
+                );
+                response.applied.push(change);
+            } else {
+                response.requires_restart.push(change);
+            }
+        }
+
+        // only apply the changes once they are all known to be valid:
+        for change in live_changes {
+            self.apply(change);
+        }
+        for change in &response.applied {
+            match &change.new {
+                Some(value) => running.insert(change.setting.clone(), value.clone()),
+                None => running.remove(&change.setting),
+            };
+        }
+
+        info!(
+            path = ?self.startup.path(),
+            applied = ?response.applied,
+            requires_restart = ?response.requires_restart,
+            overridden = ?response.overridden,
+            "reloaded config file"
+        );
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> Result<BTreeMap<String, String>, Error> {
+        parse_settings(Path::new("influxdb3.toml"), contents)
+    }
+
+    fn settings(contents: &str) -> BTreeMap<String, String> {
+        parse(contents).unwrap()
+    }
+
+    #[test]
+    fn parse_settings_as_option_values() {
+        let settings = settings(
+            r#"
+            node-id = "node0"
+            query-file-limit = 500
+            parquet-mem-cache-prune-percentage = 0.5
+            disable-parquet-mem-cache = true
+            "#,
+        );
+        assert_eq!(
+            vec![
+                ("disable-parquet-mem-cache", "true"),
+                ("node-id", "node0"),
+                ("parquet-mem-cache-prune-percentage", "0.5"),
+                ("query-file-limit", "500"),
+            ],
+            settings
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn parse_rejects_invalid_settings() {
+        for (contents, expected) in [
+            ("node-idd = \"node0\"", "unknown setting 'node-idd'"),
+            (
+                "config-file = \"other.toml\"",
+                "unknown setting 'config-file'",
+            ),
+            (
+                "node-id = [\"node0\"]",
+                "invalid value for setting 'node-id'",
+            ),
+        ] {
+            let error = parse(contents).expect_err(contents).to_string();
+            assert!(error.starts_with(expected), "{contents}: {error}");
+        }
+        assert!(matches!(parse("node-id = "), Err(Error::Parse { .. })));
+    }
+
+    #[test]
+    fn options_given_on_the_command_line() {
+        let matches = super::super::Config::command()
+            .try_get_matches_from([
+                "serve",
+                "--node-id",
+                "node0",
+                "--query-file-limit=500",
+                "--object-store",
+                "memory",
+            ])
+            .unwrap();
+        let given = given_options(&matches);
+        assert!(given.contains("node-id"));
+        assert!(given.contains("query-file-limit"));
+        // options that take their default value are not given:
+        assert!(!given.contains("max-http-request-size"));
+        assert!(!given.contains("gen1-duration"));
+    }
+
+    #[test]
+    fn changes_between_files() {
+        let old = settings("node-id = \"node0\"\nquery-file-limit = 500\ngen1-duration = \"10m\"");
+        let new = settings("node-id = \"node0\"\nquery-file-limit = 600\nlog-filter = \"debug\"");
+        let change = |setting: &str, old: Option<&str>, new: Option<&str>| ConfigSettingChange {
+            setting: setting.to_string(),
+            old: old.map(str::to_string),
+            new: new.map(str::to_string),
+        };
+        assert_eq!(
+            vec![
+                change("gen1-duration", Some("10m"), None),
+                change("log-filter", None, Some("debug")),
+                change("query-file-limit", Some("500"), Some("600")),
+            ],
+            changes(&old, &new)
+        );
+        assert!(changes(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn parse_live_changes() {
+        let change = |setting: &str, new: Option<&str>| ConfigSettingChange {
+            setting: setting.to_string(),
+            old: None,
+            new: new.map(str::to_string),
+        };
+        assert!(matches!(
+            LiveChange::parse(&change("query-file-limit", Some("600"))),
+            Ok(LiveChange::QueryFileLimit(Some(600)))
+        ));
+        assert!(matches!(
+            LiveChange::parse(&change("query-file-limit", None)),
+            Ok(LiveChange::QueryFileLimit(None))
+        ));
+        // removing a setting from the file restores its default:
+        assert!(matches!(
+            LiveChange::parse(&change("max-http-request-size", None)),
+            Ok(LiveChange::MaxHttpRequestSize(10485760))
+        ));
+        assert!(
+            LiveChange::parse(&change("log-filter", Some("info,influxdb3_write=debug"))).is_ok()
+        );
+        assert!(LiveChange::parse(&change("log-filter", Some("info,influxdb3=loud"))).is_err());
+        assert!(LiveChange::parse(&change("query-file-limit", Some("many"))).is_err());
+    }
+}
@@ -10,6 +10,7 @@ clippy::clone_on_ref_ptr,
 clippy::future_not_send
 )]
 
+use clap::CommandFactory;
 use dotenvy::dotenv;
 use influxdb3_clap_blocks::tokio::TokioIoConfig;
 use influxdb3_process::VERSION_STRING;
@@ -21,10 +22,14 @@ use std::{
 };
 use trogging::{
     TroggingGuard,
-    cli::LoggingConfigBuilderExt,
-    tracing_subscriber::{Registry, prelude::*},
+    tracing_subscriber::{Registry, prelude::*, reload},
 };
 
+use crate::commands::serve::config_file::{ConfigFile, LogLayerHandle, build_log_layer};
+
+/// The log filter used when none is given
+pub(crate) const DEFAULT_LOG_FILTER: &str = "info";
+
 pub mod commands {
     pub(crate) mod common;
     pub mod create;
@@ -33,6 +38,7 @@ pub mod commands {
     pub mod enable;
     pub mod install;
     pub mod query;
+    pub mod restore;
     pub mod serve;
     pub mod show;
     pub mod test;
@@ -105,6 +111,9 @@ enum Command {
     /// Perform a query against a running InfluxDB 3 Core server
     Query(commands::query::Config),
 
+    /// Restore a node from a backup, while no server is running for it
+    Restore(commands::restore::Config),
+
     /// Run the InfluxDB 3 Core server
     Serve(commands::serve::Config),
 
@@ -131,14 +140,18 @@ fn main() -> Result<(), std::io::Error> {
     // load all environment variables from .env before doing anything
     load_dotenv();
 
+    // the settings in the config file, if any, are loaded into the environment in the same way
+    // as the .env file, before the config is parsed
+    let config_file = find_config_file().map(|(path, matches)| load_config_file(&path, &matches));
+
     let config: Config = clap::Parser::parse();
 
     let tokio_runtime = config.runtime_config.builder()?.build()?;
 
     tokio_runtime.block_on(async move {
-        fn handle_init_logs(r: Result<TroggingGuard, trogging::Error>) -> TroggingGuard {
+        fn handle_init_logs<T>(r: Result<T, Box<dyn std::error::Error>>) -> T {
             match r {
-                Ok(guard) => guard,
+                Ok(logs) => logs,
                 Err(e) => {
                     eprintln!("Initializing logs failed: {e}");
                     std::process::exit(ReturnCode::Failure as _);
@@ -173,9 +186,9 @@ fn main() -> Result<(), std::io::Error> {
                 }
             }
             Some(Command::Serve(config)) => {
-                let _tracing_guard =
+                let (_tracing_guard, log_layer) =
                     handle_init_logs(init_logs_and_tracing(&config.logging_config));
-                if let Err(e) = commands::serve::command(config).await {
+                if let Err(e) = commands::serve::command(config, config_file, log_layer).await {
                     eprintln!("Serve command failed: {e}");
                     std::process::exit(ReturnCode::Failure as _)
                 }
@@ -204,6 +217,12 @@ fn main() -> Result<(), std::io::Error> {
                     std::process::exit(ReturnCode::Failure as _)
                 }
             }
+            Some(Command::Restore(config)) => {
+                if let Err(e) = commands::restore::command(config).await {
+                    eprintln!("Restore command failed: {e}");
+                    std::process::exit(ReturnCode::Failure as _)
+                }
+            }
             Some(Command::Write(config)) => {
                 if let Err(e) = commands::write::command(config).await {
                     eprintln!("Write command failed: {e}");
@@ -235,6 +254,45 @@ fn load_dotenv() {
     };
 }
 
+/// Find the config file given to the serve command, along with the matches of the serve
+/// command, without validating the rest of the command line, since options that are required
+/// may be set in the file.
+///
+/// Any errors in the command line are reported when the config is parsed.
+fn find_config_file() -> Option<(PathBuf, clap::ArgMatches)> {
+    let matches = Config::command()
+        .ignore_errors(true)
+        .try_get_matches()
+        .ok()?;
+    let serve_matches = matches.subcommand_matches("serve")?;
+    let path = serve_matches
+        .try_get_one::<PathBuf>("config_file")
+        .ok()
+        .flatten()?
+        .clone();
+    Some((path, serve_matches.clone()))
+}
+
+/// Load the config file given to the serve command, setting the environment variables of the
+/// options in it, before initialising the Config struct.
+///
+/// Precedence is given to existing env variables and command line arguments, as found in the
+/// `matches` of the serve command.
+fn load_config_file(path: &std::path::Path, matches: &clap::ArgMatches) -> ConfigFile {
+    match ConfigFile::load(path) {
+        Ok(mut config_file) => {
+            // SAFETY: no other threads have been started yet
+            unsafe { config_file.apply_to_env(matches) };
+            config_file
+        }
+        Err(e) => {
+            eprintln!("FATAL Error loading config from: {e}");
+            eprintln!("Aborting");
+            std::process::exit(1);
+        }
+    }
+}
+
 // Based on ideas from
 // https://github.com/servo/servo/blob/f03ddf6c6c6e94e799ab2a3a89660aea4a01da6f/ports/servo/main.rs#L58-L79
 #[cfg(unix)]
@@ -289,11 +347,10 @@ unsafe fn set_signal_handler(signal: libc::c_int, handler: unsafe extern "C" fn(
 
 fn init_logs_and_tracing(
     config: &trogging::cli::LoggingConfig,
-) -> Result<TroggingGuard, trogging::Error> {
-    let log_layer = trogging::Builder::new()
-        .with_default_log_filter("info")
-        .with_logging_config(config)
-        .build()?;
+) -> Result<(TroggingGuard, LogLayerHandle), Box<dyn std::error::Error>> {
+    // the log layer is replaced with one built from the new log filter when the config file is
+    // reloaded:
+    let (log_layer, log_layer_handle) = reload::Layer::new(build_log_layer(config, None)?);
 
     let layers = log_layer;
 
@@ -309,7 +366,8 @@ fn init_logs_and_tracing(
     };
 
     let subscriber = Registry::default().with(layers);
-    trogging::install_global(subscriber)
+    let guard = trogging::install_global(subscriber)?;
+    Ok((guard, log_layer_handle))
 }
 
 // XXX: this should be somewhere more appropriate
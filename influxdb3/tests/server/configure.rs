@@ -1,3 +1,5 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use hyper::StatusCode;
 use observability_deps::tracing::debug;
 use pretty_assertions::assert_eq;
@@ -1317,3 +1319,304 @@ async fn try_deleting_table_after_db_is_deleted() {
         .expect("delete table call succeed");
     assert_eq!(StatusCode::NOT_FOUND, resp.status());
 }
+
+#[test_log::test(tokio::test)]
+async fn api_v3_configure_backup() {
+    let server = TestServer::spawn().await;
+    let client = reqwest::Client::new();
+    let url = format!(
+        "{base}/api/v3/configure/backup",
+        base = server.client_addr()
+    );
+    server
+        .write_lp_to_db(
+            "foo",
+            "cpu,host=a usage=0.5 1000",
+            influxdb3_client::Precision::Second,
+        )
+        .await
+        .expect("write to db");
+
+    let resp = client.get(&url).send().await.expect("list backups");
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!(json!([]), resp.json::<Value>().await.unwrap());
+
+    // the write has not been snapshotted, so the backup is made of the catalog and its WAL file:
+    let resp = client.post(&url).send().await.expect("create backup");
+    assert_eq!(StatusCode::CREATED, resp.status());
+    let first = resp.json::<Value>().await.unwrap();
+    assert_eq!(0, first["backup_sequence_number"]);
+    assert_eq!(2, first["file_count"]);
+    assert_eq!(2, first["copied_file_count"]);
+
+    // nothing has changed, so nothing is copied by the next backup:
+    let resp = client.post(&url).send().await.expect("create backup");
+    assert_eq!(StatusCode::CREATED, resp.status());
+    let second = resp.json::<Value>().await.unwrap();
+    assert_eq!(1, second["backup_sequence_number"]);
+    assert_eq!(2, second["file_count"]);
+    assert_eq!(0, second["copied_file_count"]);
+
+    let resp = client.get(&url).send().await.expect("list backups");
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!(json!([second, first]), resp.json::<Value>().await.unwrap());
+
+    // exactly one of the sequence number or the number of backups to keep is required:
+    for query in ["", "?backup_sequence_number=0&keep=1"] {
+        let resp = client.delete(format!("{url}{query}")).send().await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status(), "{query}");
+    }
+    let resp = client
+        .delete(format!("{url}?backup_sequence_number=42"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NOT_FOUND, resp.status());
+
+    let resp = client.delete(format!("{url}?keep=1")).send().await.unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!(json!([first]), resp.json::<Value>().await.unwrap());
+    let resp = client
+        .delete(format!("{url}?backup_sequence_number=1"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!(json!([second]), resp.json::<Value>().await.unwrap());
+
+    let resp = client.get(&url).send().await.expect("list backups");
+    assert_eq!(json!([]), resp.json::<Value>().await.unwrap());
+}
+
+#[tokio::test]
+async fn api_v3_configure_downsampling_task() {
+    let server = TestServer::spawn().await;
+    let client = reqwest::Client::new();
+    let url = format!(
+        "{base}/api/v3/configure/downsampling_task",
+        base = server.client_addr()
+    );
+    server
+        .write_lp_to_db(
+            "foo",
+            "cpu,host=a usage=0.5 1",
+            influxdb3_client::Precision::Second,
+        )
+        .await
+        .expect("write to db");
+
+    let task = json!({
+        "db": "foo",
+        "task_name": "cpu_1s",
+        "source_table": "cpu",
+        "target_db": "foo_1s",
+        "window": "1s",
+        "delay": "2s",
+        "aggregate": "mean",
+    });
+    for (setting, value) in [("window", "1500ms"), ("delay", "soon")] {
+        let mut invalid = task.clone();
+        invalid[setting] = json!(value);
+        let resp = client.post(&url).json(&invalid).send().await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status(), "{setting}");
+    }
+
+    let resp = client.post(&url).json(&task).send().await.unwrap();
+    assert_eq!(StatusCode::CREATED, resp.status());
+    let mut conflicting = task.clone();
+    conflicting["aggregate"] = json!("max");
+    let resp = client.post(&url).json(&conflicting).send().await.unwrap();
+    assert_eq!(StatusCode::CONFLICT, resp.status());
+
+    // write to the current window, which is downsampled once it closes and the delay has passed:
+    let second = 1_000_000_000;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as i64;
+    let window_start = now - now % second;
+    server
+        .write_lp_to_db(
+            "foo",
+            format!(
+                "cpu,host=a usage=1 {}\ncpu,host=a usage=2 {}",
+                window_start + 1,
+                window_start + 2
+            ),
+            influxdb3_client::Precision::Nanosecond,
+        )
+        .await
+        .expect("write to db");
+
+    // a write for the window that arrives after it closed, but within the delay, is included:
+    let closed = window_start + second - now;
+    tokio::time::sleep(std::time::Duration::from_nanos(closed as u64 + 100_000_000)).await;
+    server
+        .write_lp_to_db(
+            "foo",
+            format!("cpu,host=b usage=0.5 {}", window_start + 3),
+            influxdb3_client::Precision::Nanosecond,
+        )
+        .await
+        .expect("write to db");
+    let mut downsampled = Value::Null;
+    for _ in 0..100 {
+        let resp = server
+            .api_v3_query_sql(&[
+                ("db", "foo_1s"),
+                ("q", "SELECT host, usage FROM cpu ORDER BY host"),
+                ("format", "json"),
+            ])
+            .await;
+        if resp.status() == StatusCode::OK {
+            downsampled = resp.json::<Value>().await.unwrap();
+            if downsampled.as_array().is_some_and(|rows| rows.len() == 2) {
+                break;
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(
+        json!([{"host": "a", "usage": 1.5}, {"host": "b", "usage": 0.5}]),
+        downsampled
+    );
+
+    let resp = client
+        .post(format!("{url}/disable"))
+        .query(&[("db", "foo"), ("task_name", "cpu_1s")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    let resp = client
+        .get(&url)
+        .query(&[("db", "foo")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    let tasks = resp.json::<Value>().await.unwrap();
+    debug!(?tasks, "listed downsampling tasks");
+    assert_eq!(1, tasks.as_array().unwrap().len());
+    assert_eq!("cpu_1s", tasks[0]["task_name"]);
+    assert_eq!("cpu", tasks[0]["target_table"]);
+    assert_eq!("2s", tasks[0]["delay"]);
+    assert_eq!(true, tasks[0]["disabled"]);
+    assert_eq!(0, tasks[0]["consecutive_failures"]);
+    assert!(tasks[0]["watermark"].as_i64().unwrap() > 0);
+
+    let resp = client
+        .delete(&url)
+        .query(&[("db", "foo"), ("task_name", "cpu_1s")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    let resp = client
+        .get(&url)
+        .query(&[("db", "foo")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(json!([]), resp.json::<Value>().await.unwrap());
+    let resp = client
+        .post(format!("{url}/enable"))
+        .query(&[("db", "foo"), ("task_name", "cpu_1s")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NOT_FOUND, resp.status());
+}
+
+#[test_log::test(tokio::test)]
+async fn api_v3_configure_database_quota() {
+    let server = TestServer::spawn().await;
+    let client = reqwest::Client::new();
+    let base = server.client_addr();
+    let quota_url = format!("{base}/api/v3/configure/database/quota");
+    let usage_url = format!("{base}/api/v3/configure/database/usage");
+    let write_url = format!("{base}/api/v3/write_lp");
+    server
+        .write_lp_to_db(
+            "foo",
+            "cpu,host=a usage=0.5 1",
+            influxdb3_client::Precision::Second,
+        )
+        .await
+        .expect("write to db");
+
+    let resp = client
+        .post(&quota_url)
+        .json(&json!({"db": "bar", "max_series": 2}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NOT_FOUND, resp.status());
+    let resp = client
+        .post(&quota_url)
+        .json(&json!({"db": "foo", "max_series": 2, "max_concurrent_queries": 0}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+
+    // one more series fits in the quota, existing series can still be written to after that:
+    for (lp, status) in [
+        ("cpu,host=b usage=0.5 2", StatusCode::NO_CONTENT),
+        ("cpu,host=c usage=0.5 3", StatusCode::TOO_MANY_REQUESTS),
+        ("cpu,host=a usage=1 4", StatusCode::NO_CONTENT),
+    ] {
+        let resp = client
+            .post(&write_url)
+            .query(&[("db", "foo"), ("precision", "second")])
+            .body(lp)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(status, resp.status(), "write {lp}");
+    }
+
+    let resp = server
+        .api_v3_query_sql(&[
+            ("db", "foo"),
+            ("q", "SELECT * FROM cpu"),
+            ("format", "json"),
+        ])
+        .await;
+    assert_eq!(StatusCode::TOO_MANY_REQUESTS, resp.status());
+
+    let usage = client
+        .get(&usage_url)
+        .query(&[("db", "foo")])
+        .send()
+        .await
+        .unwrap()
+        .json::<Value>()
+        .await
+        .unwrap();
+    debug!(?usage, "database usage");
+    assert_eq!("foo", usage[0]["db"]);
+    assert_eq!(2, usage[0]["max_series"]);
+    assert_eq!(Value::Null, usage[0]["max_stored_bytes"]);
+    assert_eq!(2, usage[0]["series"]);
+    assert_eq!(0, usage[0]["running_queries"]);
+    assert_eq!(1, usage[0]["rejected_writes"]);
+    assert_eq!(1, usage[0]["rejected_queries"]);
+
+    // removing the quota lifts the limits:
+    let resp = client
+        .post(&quota_url)
+        .json(&json!({"db": "foo"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    let resp = server
+        .api_v3_query_sql(&[
+            ("db", "foo"),
+            ("q", "SELECT * FROM cpu"),
+            ("format", "json"),
+        ])
+        .await;
+    assert_eq!(StatusCode::OK, resp.status());
+}
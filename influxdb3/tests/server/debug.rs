@@ -0,0 +1,57 @@
+use reqwest::StatusCode;
+use serde_json::Value;
+
+use crate::server::{ConfigProvider, TestServer};
+
+#[tokio::test]
+async fn debug_endpoints_disabled_by_default() {
+    let server = TestServer::spawn().await;
+    let client = reqwest::Client::new();
+
+    for path in ["/debug/pprof/profile", "/debug/pprof/heap"] {
+        let resp = client
+            .get(format!("{base}{path}", base = server.client_addr()))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, resp.status(), "path: {path}");
+    }
+}
+
+#[tokio::test]
+async fn debug_pprof_heap() {
+    let server = TestServer::configure().with_debug_endpoints().spawn().await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .get(format!(
+            "{base}/debug/pprof/heap",
+            base = server.client_addr()
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    let json = resp.json::<Value>().await.unwrap();
+    let map = json.as_object().unwrap();
+    assert!(map["allocated_bytes"].as_u64().unwrap() > 0);
+    assert!(map["resident_bytes"].as_u64().unwrap() > 0);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn debug_pprof_profile() {
+    let server = TestServer::configure().with_debug_endpoints().spawn().await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .get(format!(
+            "{base}/debug/pprof/profile?seconds=1",
+            base = server.client_addr()
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    assert!(!resp.bytes().await.unwrap().is_empty());
+}
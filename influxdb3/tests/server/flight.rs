@@ -2,6 +2,8 @@ use arrow_flight::Ticket;
 use arrow_flight::sql::SqlInfo;
 use arrow_util::assert_batches_eq;
 use arrow_util::assert_batches_sorted_eq;
+use futures::TryStreamExt;
+use hyper::StatusCode;
 use influxdb3_client::Precision;
 use serde_json::json;
 use test_helpers::assert_contains;
@@ -377,3 +379,70 @@ async fn test_flight_distinct_cache() {
         );
     }
 }
+
+#[test_log::test(tokio::test)]
+async fn flight_database_quota() {
+    let server = TestServer::spawn().await;
+    server
+        .write_lp_to_db(
+            "foo",
+            "cpu,host=a usage=0.5 1\ncpu,host=b usage=0.6 2",
+            Precision::Second,
+        )
+        .await
+        .unwrap();
+    let http_client = reqwest::Client::new();
+    let quota_url = format!(
+        "{base}/api/v3/configure/database/quota",
+        base = server.client_addr()
+    );
+    let set_quota = |quota: serde_json::Value| {
+        let request = http_client.post(&quota_url).json(&quota);
+        async move {
+            let resp = request.send().await.unwrap();
+            assert_eq!(StatusCode::OK, resp.status());
+        }
+    };
+    let mut client = server.flight_sql_client("foo").await;
+    let query = "SELECT host, count(*) FROM cpu GROUP BY host";
+
+    set_quota(json!({"db": "foo", "max_concurrent_queries": 0})).await;
+    let error = client.query(query).await.unwrap_err();
+    assert_contains!(error.to_string(), "limit of 0 concurrent queries");
+
+    // the memory a query uses while it runs is limited, so a query that has to aggregate fails:
+    set_quota(json!({"db": "foo", "max_query_memory_bytes": 1})).await;
+    let error = match client.query(query).await {
+        Ok(stream) => stream.try_collect::<Vec<_>>().await.unwrap_err(),
+        Err(error) => error,
+    };
+    assert_contains!(error.to_string(), "Resources exhausted");
+
+    let usage = http_client
+        .get(format!(
+            "{base}/api/v3/configure/database/usage",
+            base = server.client_addr()
+        ))
+        .query(&[("db", "foo")])
+        .send()
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(2, usage[0]["rejected_queries"]);
+
+    set_quota(json!({"db": "foo"})).await;
+    let batches = collect_stream(client.query(query).await.unwrap()).await;
+    assert_batches_sorted_eq!(
+        [
+            "+------+----------+",
+            "| host | count(*) |",
+            "+------+----------+",
+            "| a    | 1        |",
+            "| b    | 1        |",
+            "+------+----------+",
+        ],
+        &batches
+    );
+}
@@ -17,12 +17,14 @@ use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 mod auth;
 mod client;
 mod configure;
+mod debug;
 mod flight;
 mod limits;
 #[cfg(feature = "system-py")]
 mod packages;
 mod ping;
 mod query;
+mod reload;
 mod system_tables;
 mod write;
 
@@ -55,6 +57,9 @@ pub struct TestConfig {
     package_manager: Option<String>,
     // If None, use memory object store.
     object_store_dir: Option<String>,
+    debug_endpoints: bool,
+    config_file: Option<String>,
+    without_node_id: bool,
 }
 
 impl TestConfig {
@@ -74,6 +79,13 @@ impl TestConfig {
         self
     }
 
+    /// Leave the node id off the command line of the spawned [`TestServer`], so that it must
+    /// be set in the config file
+    pub fn without_node_id(mut self) -> Self {
+        self.without_node_id = true;
+        self
+    }
+
     /// Set the plugin dir for this [`TestServer`]
     pub fn with_plugin_dir<S: Into<String>>(mut self, plugin_dir: S) -> Self {
         self.plugin_dir = Some(plugin_dir.into());
@@ -96,6 +108,18 @@ impl TestConfig {
         self.object_store_dir = Some(object_store_dir.into());
         self
     }
+
+    /// Enable the `/debug/pprof/*` endpoints on the spawned [`TestServer`]
+    pub fn with_debug_endpoints(mut self) -> Self {
+        self.debug_endpoints = true;
+        self
+    }
+
+    /// Set the config file for the spawned [`TestServer`]
+    pub fn with_config_file<S: Into<String>>(mut self, config_file: S) -> Self {
+        self.config_file = Some(config_file.into());
+        self
+    }
 }
 
 impl ConfigProvider for TestConfig {
@@ -119,11 +143,22 @@ impl ConfigProvider for TestConfig {
                 package_manager.to_owned(),
             ]);
         }
-        args.push("--node-id".to_string());
-        if let Some(host) = &self.node_id {
-            args.push(host.to_owned());
-        } else {
-            args.push("test-server".to_string());
+        if self.debug_endpoints {
+            args.push("--enable-debug-endpoints".to_string());
+        }
+        if let Some(config_file) = &self.config_file {
+            args.append(&mut vec![
+                "--config-file".to_string(),
+                config_file.to_owned(),
+            ]);
+        }
+        if !self.without_node_id {
+            args.push("--node-id".to_string());
+            if let Some(host) = &self.node_id {
+                args.push(host.to_owned());
+            } else {
+                args.push("test-server".to_string());
+            }
         }
         if let Some(object_store_dir) = &self.object_store_dir {
             args.append(&mut vec![
@@ -0,0 +1,190 @@
+use hyper::StatusCode;
+use influxdb3_client::Precision;
+use influxdb3_types::http::{ConfigReloadResponse, ConfigSettingChange};
+use pretty_assertions::assert_eq;
+use tempfile::NamedTempFile;
+use test_helpers::assert_contains;
+
+use crate::server::{ConfigProvider, TestServer};
+
+fn change(setting: &str, old: Option<&str>, new: Option<&str>) -> ConfigSettingChange {
+    ConfigSettingChange {
+        setting: setting.to_string(),
+        old: old.map(str::to_string),
+        new: new.map(str::to_string),
+    }
+}
+
+#[tokio::test]
+async fn api_v3_configure_reload() {
+    let config_file = NamedTempFile::new().unwrap();
+    std::fs::write(
+        config_file.path(),
+        r#"
+        node-id = "file-node"
+        gen1-duration = "1m"
+        query-file-limit = 500
+        "#,
+    )
+    .unwrap();
+    let server = TestServer::configure()
+        .with_config_file(config_file.path().to_str().unwrap())
+        .spawn()
+        .await;
+    let client = reqwest::Client::new();
+    let url = format!(
+        "{base}/api/v3/configure/reload",
+        base = server.client_addr()
+    );
+
+    // reloading the unchanged file changes nothing:
+    let resp = client.post(&url).send().await.unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!(
+        ConfigReloadResponse::default(),
+        resp.json::<ConfigReloadResponse>().await.unwrap()
+    );
+
+    std::fs::write(
+        config_file.path(),
+        r#"
+        node-id = "other-node"
+        gen1-duration = "5m"
+        max-http-request-size = 100
+        "#,
+    )
+    .unwrap();
+    let resp = client.post(&url).send().await.unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!(
+        ConfigReloadResponse {
+            applied: vec![
+                change("max-http-request-size", None, Some("100")),
+                change("query-file-limit", Some("500"), None),
+            ],
+            requires_restart: vec![change("gen1-duration", Some("1m"), Some("5m"))],
+            // the node id is given on the command line of the test server:
+            overridden: vec![change("node-id", Some("file-node"), Some("other-node"))],
+        },
+        resp.json::<ConfigReloadResponse>().await.unwrap()
+    );
+
+    // the new request size limit is applied:
+    server
+        .write_lp_to_db("foo", "cpu,host=a usage=0.5 1", Precision::Second)
+        .await
+        .expect("small write");
+    server
+        .write_lp_to_db(
+            "foo",
+            "cpu,host=a usage=0.5 1\n".repeat(10),
+            Precision::Second,
+        )
+        .await
+        .expect_err("write larger than the request size limit");
+
+    // settings that require a restart are reported until the server is restarted:
+    let resp = client.post(&url).send().await.unwrap();
+    assert_eq!(
+        ConfigReloadResponse {
+            requires_restart: vec![change("gen1-duration", Some("1m"), Some("5m"))],
+            overridden: vec![change("node-id", Some("file-node"), Some("other-node"))],
+            ..Default::default()
+        },
+        resp.json::<ConfigReloadResponse>().await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn required_options_in_config_file() {
+    let config_file = NamedTempFile::new().unwrap();
+    std::fs::write(config_file.path(), "node-id = \"file-node\"\n").unwrap();
+    let server = TestServer::configure()
+        .with_config_file(config_file.path().to_str().unwrap())
+        .without_node_id()
+        .spawn()
+        .await;
+    server
+        .write_lp_to_db("foo", "cpu,host=a usage=0.5 1", Precision::Second)
+        .await
+        .expect("write to the server started with the node id in the config file");
+
+    // the node id is taken from the file, so a change to it requires a restart:
+    std::fs::write(config_file.path(), "node-id = \"other-node\"\n").unwrap();
+    let resp = reqwest::Client::new()
+        .post(format!(
+            "{base}/api/v3/configure/reload",
+            base = server.client_addr()
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!(
+        ConfigReloadResponse {
+            requires_restart: vec![change("node-id", Some("file-node"), Some("other-node"))],
+            ..Default::default()
+        },
+        resp.json::<ConfigReloadResponse>().await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn api_v3_configure_reload_invalid_config() {
+    let config_file = NamedTempFile::new().unwrap();
+    std::fs::write(config_file.path(), "max-http-request-size = 100000\n").unwrap();
+    let server = TestServer::configure()
+        .with_config_file(config_file.path().to_str().unwrap())
+        .spawn()
+        .await;
+    let client = reqwest::Client::new();
+    let url = format!(
+        "{base}/api/v3/configure/reload",
+        base = server.client_addr()
+    );
+
+    for (contents, expected) in [
+        (
+            "max-http-request-size = \"lots\"",
+            "invalid value for setting 'max-http-request-size'",
+        ),
+        (
+            "max-http-request-sise = 100",
+            "unknown setting 'max-http-request-sise'",
+        ),
+        ("max-http-request-size = ", "unable to parse config file"),
+        // no settings are applied if any of them are invalid:
+        (
+            "max-http-request-size = 100\nquery-file-limit = -1",
+            "invalid value for setting 'query-file-limit'",
+        ),
+    ] {
+        std::fs::write(config_file.path(), contents).unwrap();
+        let resp = client.post(&url).send().await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, resp.status(), "{contents}");
+        assert_contains!(resp.text().await.unwrap(), expected);
+    }
+
+    server
+        .write_lp_to_db(
+            "foo",
+            "cpu,host=a usage=0.5 1\n".repeat(10),
+            Precision::Second,
+        )
+        .await
+        .expect("write within the request size limit of the running server");
+}
+
+#[tokio::test]
+async fn api_v3_configure_reload_without_config_file() {
+    let server = TestServer::spawn().await;
+    let resp = reqwest::Client::new()
+        .post(format!(
+            "{base}/api/v3/configure/reload",
+            base = server.client_addr()
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(StatusCode::NOT_FOUND, resp.status());
+}
@@ -1273,6 +1273,8 @@ mod tests {
                 map
             },
             processing_engine_triggers: Default::default(),
+            downsampling_tasks: Default::default(),
+            quota: Default::default(),
             deleted: false,
         };
         let table_id = TableId::from(0);
@@ -10,10 +10,11 @@ use indexmap::IndexMap;
 use influxdb_line_protocol::FieldValue;
 use influxdb3_id::{ColumnId, DbId, SerdeVecMap, TableId};
 use influxdb3_wal::{
-    CatalogBatch, CatalogOp, DeleteDatabaseDefinition, DeleteTableDefinition,
-    DeleteTriggerDefinition, DistinctCacheDefinition, DistinctCacheDelete, FieldAdditions,
-    FieldDefinition, LastCacheDefinition, LastCacheDelete, OrderedCatalogBatch, TriggerDefinition,
-    TriggerIdentifier,
+    CatalogBatch, CatalogOp, DatabaseQuota, DeleteDatabaseDefinition, DeleteTableDefinition,
+    DeleteTriggerDefinition, DistinctCacheDefinition, DistinctCacheDelete,
+    DownsamplingTaskDefinition, DownsamplingTaskIdentifier, DownsamplingTaskWatermark,
+    FieldAdditions, FieldDefinition, LastCacheDefinition, LastCacheDelete, OrderedCatalogBatch,
+    TriggerDefinition, TriggerIdentifier,
 };
 use iox_time::Time;
 use observability_deps::tracing::{debug, info, warn};
@@ -134,6 +135,22 @@ pub enum Error {
     },
     #[error("failed to parse trigger from {}", trigger_spec)]
     ProcessingEngineTriggerSpecParseError { trigger_spec: String },
+
+    #[error(
+        "Cannot overwrite Downsampling Task {} in Database {}",
+        task_name,
+        database_name
+    )]
+    DownsamplingTaskExists {
+        database_name: String,
+        task_name: String,
+    },
+
+    #[error("Downsampling Task {} not in DB {}", task_name, database_name)]
+    DownsamplingTaskNotFound {
+        database_name: String,
+        task_name: String,
+    },
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -332,6 +349,23 @@ impl Catalog {
         result
     }
 
+    /// The database and task name of every downsampling task that is not disabled
+    pub fn active_downsampling_tasks(&self) -> Vec<(String, String)> {
+        let inner = self.inner.read();
+        inner
+            .databases
+            .values()
+            .filter(|schema| !schema.deleted)
+            .flat_map(|schema| {
+                schema
+                    .downsampling_tasks
+                    .iter()
+                    .filter(|(_, task)| !task.disabled)
+                    .map(move |(key, _)| (schema.name.to_string(), key.to_string()))
+            })
+            .collect()
+    }
+
     pub fn inner(&self) -> &RwLock<InnerCatalog> {
         &self.inner
     }
@@ -463,6 +497,8 @@ pub struct DatabaseSchema {
     pub tables: SerdeVecMap<TableId, Arc<TableDefinition>>,
     pub table_map: BiHashMap<TableId, Arc<str>>,
     pub processing_engine_triggers: HashMap<String, TriggerDefinition>,
+    pub downsampling_tasks: HashMap<String, DownsamplingTaskDefinition>,
+    pub quota: DatabaseQuota,
     pub deleted: bool,
 }
 
@@ -474,6 +510,8 @@ impl DatabaseSchema {
             tables: Default::default(),
             table_map: BiHashMap::new(),
             processing_engine_triggers: HashMap::new(),
+            downsampling_tasks: HashMap::new(),
+            quota: DatabaseQuota::default(),
             deleted: false,
         }
     }
@@ -660,6 +698,18 @@ impl UpdateDatabaseSchema for CatalogOp {
             CatalogOp::DisableTrigger(trigger_identifier) => {
                 DisableTrigger(trigger_identifier.clone()).update_schema(schema)
             }
+            CatalogOp::CreateDownsamplingTask(create_task) => create_task.update_schema(schema),
+            CatalogOp::DeleteDownsamplingTask(task_identifier) => {
+                DeleteDownsamplingTask(task_identifier.clone()).update_schema(schema)
+            }
+            CatalogOp::EnableDownsamplingTask(task_identifier) => {
+                EnableDownsamplingTask(task_identifier.clone()).update_schema(schema)
+            }
+            CatalogOp::DisableDownsamplingTask(task_identifier) => {
+                DisableDownsamplingTask(task_identifier.clone()).update_schema(schema)
+            }
+            CatalogOp::AdvanceDownsamplingTask(watermark) => watermark.update_schema(schema),
+            CatalogOp::SetDatabaseQuota(quota) => quota.update_schema(schema),
         }
     }
 }
@@ -826,6 +876,122 @@ impl UpdateDatabaseSchema for DeleteTriggerDefinition {
     }
 }
 
+impl UpdateDatabaseSchema for DownsamplingTaskDefinition {
+    fn update_schema<'a>(
+        &self,
+        mut schema: Cow<'a, DatabaseSchema>,
+    ) -> Result<Cow<'a, DatabaseSchema>> {
+        if let Some(current) = schema.downsampling_tasks.get(&self.task_name) {
+            if current == self {
+                return Ok(schema);
+            }
+            return Err(Error::DownsamplingTaskExists {
+                database_name: schema.name.to_string(),
+                task_name: self.task_name.to_string(),
+            });
+        }
+        schema
+            .to_mut()
+            .downsampling_tasks
+            .insert(self.task_name.to_string(), self.clone());
+        Ok(schema)
+    }
+}
+
+struct DeleteDownsamplingTask(DownsamplingTaskIdentifier);
+struct EnableDownsamplingTask(DownsamplingTaskIdentifier);
+struct DisableDownsamplingTask(DownsamplingTaskIdentifier);
+
+impl UpdateDatabaseSchema for DeleteDownsamplingTask {
+    fn update_schema<'a>(
+        &self,
+        mut schema: Cow<'a, DatabaseSchema>,
+    ) -> Result<Cow<'a, DatabaseSchema>> {
+        // deleting a non-existent task is a no-op to make it idempotent.
+        if !schema.downsampling_tasks.contains_key(&self.0.task_name) {
+            return Ok(schema);
+        }
+        schema.to_mut().downsampling_tasks.remove(&self.0.task_name);
+        Ok(schema)
+    }
+}
+
+impl UpdateDatabaseSchema for EnableDownsamplingTask {
+    fn update_schema<'a>(
+        &self,
+        schema: Cow<'a, DatabaseSchema>,
+    ) -> Result<Cow<'a, DatabaseSchema>> {
+        set_downsampling_task_disabled(schema, &self.0, false)
+    }
+}
+
+impl UpdateDatabaseSchema for DisableDownsamplingTask {
+    fn update_schema<'a>(
+        &self,
+        schema: Cow<'a, DatabaseSchema>,
+    ) -> Result<Cow<'a, DatabaseSchema>> {
+        set_downsampling_task_disabled(schema, &self.0, true)
+    }
+}
+
+fn set_downsampling_task_disabled<'a>(
+    mut schema: Cow<'a, DatabaseSchema>,
+    task: &DownsamplingTaskIdentifier,
+    disabled: bool,
+) -> Result<Cow<'a, DatabaseSchema>> {
+    let Some(current) = schema.downsampling_tasks.get(&task.task_name) else {
+        return Err(Error::DownsamplingTaskNotFound {
+            database_name: task.db_name.to_string(),
+            task_name: task.task_name.to_string(),
+        });
+    };
+    if current.disabled == disabled {
+        return Ok(schema);
+    }
+    schema
+        .to_mut()
+        .downsampling_tasks
+        .get_mut(&task.task_name)
+        .expect("already checked containment")
+        .disabled = disabled;
+    Ok(schema)
+}
+
+impl UpdateDatabaseSchema for DownsamplingTaskWatermark {
+    fn update_schema<'a>(
+        &self,
+        mut schema: Cow<'a, DatabaseSchema>,
+    ) -> Result<Cow<'a, DatabaseSchema>> {
+        let Some(current) = schema.downsampling_tasks.get(&self.task_name) else {
+            // the task may have been deleted while a window was being written
+            return Ok(schema);
+        };
+        // the watermark only moves forward, so replaying an older update is a no-op
+        if current.watermark_ns >= self.watermark_ns {
+            return Ok(schema);
+        }
+        schema
+            .to_mut()
+            .downsampling_tasks
+            .get_mut(&self.task_name)
+            .expect("already checked containment")
+            .watermark_ns = self.watermark_ns;
+        Ok(schema)
+    }
+}
+
+impl UpdateDatabaseSchema for DatabaseQuota {
+    fn update_schema<'a>(
+        &self,
+        mut schema: Cow<'a, DatabaseSchema>,
+    ) -> Result<Cow<'a, DatabaseSchema>> {
+        if schema.quota != *self {
+            schema.to_mut().quota = *self;
+        }
+        Ok(schema)
+    }
+}
+
 fn make_new_name_using_deleted_time(name: &str, deletion_time: Time) -> Arc<str> {
     Arc::from(format!(
         "{}-{}",
@@ -1315,6 +1481,8 @@ mod tests {
                 map
             },
             processing_engine_triggers: Default::default(),
+            downsampling_tasks: Default::default(),
+            quota: Default::default(),
             deleted: false,
         };
         use InfluxColumnType::*;
@@ -1524,6 +1692,8 @@ mod tests {
             tables: SerdeVecMap::new(),
             table_map: BiHashMap::new(),
             processing_engine_triggers: Default::default(),
+            downsampling_tasks: Default::default(),
+            quota: Default::default(),
             deleted: false,
         };
         database.tables.insert(
@@ -1582,6 +1752,8 @@ mod tests {
                 map
             },
             processing_engine_triggers: Default::default(),
+            downsampling_tasks: Default::default(),
+            quota: Default::default(),
             deleted: false,
         };
         use InfluxColumnType::*;
@@ -1639,6 +1811,8 @@ mod tests {
                 map
             },
             processing_engine_triggers: Default::default(),
+            downsampling_tasks: Default::default(),
+            quota: Default::default(),
             deleted: false,
         };
         use InfluxColumnType::*;
@@ -1793,6 +1967,8 @@ mod tests {
             tables: SerdeVecMap::new(),
             table_map: BiHashMap::new(),
             processing_engine_triggers: Default::default(),
+            downsampling_tasks: Default::default(),
+            quota: Default::default(),
             deleted: false,
         };
         let deleted_table_id = TableId::new();
@@ -2173,4 +2349,149 @@ mod tests {
             .unwrap();
         assert_eq!(2_000, catalog.inner.read().table_count());
     }
+
+    #[test]
+    fn downsampling_task_lifecycle() -> Result<()> {
+        let catalog = Catalog::new(Arc::from("host"), Arc::from("instance"));
+        let db_id = DbId::new();
+        let db_name: Arc<str> = Arc::from("foo");
+        let batch = |ops| CatalogBatch {
+            database_id: db_id,
+            database_name: Arc::clone(&db_name),
+            time_ns: 0,
+            ops,
+        };
+        let task = DownsamplingTaskDefinition {
+            task_name: "cpu_1m".to_string(),
+            database_name: db_name.to_string(),
+            source_table: "cpu".to_string(),
+            target_database: "foo_1m".to_string(),
+            target_table: "cpu".to_string(),
+            window: std::time::Duration::from_secs(60),
+            delay: std::time::Duration::from_millis(1_500),
+            aggregate: influxdb3_wal::DownsamplingAggregate::Mean,
+            disabled: false,
+            watermark_ns: 0,
+        };
+        let identifier = DownsamplingTaskIdentifier {
+            db_name: db_name.to_string(),
+            task_name: task.task_name.clone(),
+        };
+        catalog.apply_catalog_batch(&batch(vec![CatalogOp::CreateDownsamplingTask(
+            task.clone(),
+        )]))?;
+        // re-creating an identical task is a no-op, but a conflicting one is an error:
+        assert!(
+            catalog
+                .apply_catalog_batch(&batch(vec![CatalogOp::CreateDownsamplingTask(
+                    task.clone()
+                )]))?
+                .is_none()
+        );
+        let conflicting = DownsamplingTaskDefinition {
+            source_table: "mem".to_string(),
+            ..task.clone()
+        };
+        assert!(matches!(
+            catalog
+                .apply_catalog_batch(&batch(vec![CatalogOp::CreateDownsamplingTask(conflicting)])),
+            Err(Error::DownsamplingTaskExists { .. })
+        ));
+        assert_eq!(
+            vec![(db_name.to_string(), task.task_name.clone())],
+            catalog.active_downsampling_tasks()
+        );
+
+        // the watermark only moves forward:
+        let advance = |watermark_ns| {
+            batch(vec![CatalogOp::AdvanceDownsamplingTask(
+                DownsamplingTaskWatermark {
+                    db_name: db_name.to_string(),
+                    task_name: task.task_name.clone(),
+                    watermark_ns,
+                },
+            )])
+        };
+        catalog.apply_catalog_batch(&advance(120_000_000_000))?;
+        assert!(
+            catalog
+                .apply_catalog_batch(&advance(60_000_000_000))?
+                .is_none()
+        );
+        let watermark =
+            || catalog.db_schema_by_id(&db_id).unwrap().downsampling_tasks["cpu_1m"].watermark_ns;
+        assert_eq!(120_000_000_000, watermark());
+
+        catalog.apply_catalog_batch(&batch(vec![CatalogOp::DisableDownsamplingTask(
+            identifier.clone(),
+        )]))?;
+        assert!(catalog.active_downsampling_tasks().is_empty());
+
+        // the task, including its state, survives serialization:
+        let serialized = serde_json::to_string(&catalog).unwrap();
+        let deserialized: Catalog = serde_json::from_str(&serialized).unwrap();
+        let restored = deserialized
+            .db_schema_by_id(&db_id)
+            .unwrap()
+            .downsampling_tasks["cpu_1m"]
+            .clone();
+        assert_eq!(
+            DownsamplingTaskDefinition {
+                disabled: true,
+                watermark_ns: 120_000_000_000,
+                ..task
+            },
+            restored
+        );
+
+        catalog.apply_catalog_batch(&batch(vec![CatalogOp::DeleteDownsamplingTask(
+            identifier.clone(),
+        )]))?;
+        assert!(
+            catalog
+                .db_schema_by_id(&db_id)
+                .unwrap()
+                .downsampling_tasks
+                .is_empty()
+        );
+        assert!(matches!(
+            catalog
+                .apply_catalog_batch(&batch(vec![CatalogOp::EnableDownsamplingTask(identifier)])),
+            Err(Error::DownsamplingTaskNotFound { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn downsampling_task_replays_after_serialization() -> Result<()> {
+        let catalog = Catalog::new(Arc::from("host"), Arc::from("instance"));
+        let db_id = DbId::new();
+        let create = CatalogBatch {
+            database_id: db_id,
+            database_name: Arc::from("foo"),
+            time_ns: 0,
+            ops: vec![CatalogOp::CreateDownsamplingTask(
+                DownsamplingTaskDefinition {
+                    task_name: "cpu_1m".to_string(),
+                    database_name: "foo".to_string(),
+                    source_table: "cpu".to_string(),
+                    target_database: "foo_1m".to_string(),
+                    target_table: "cpu".to_string(),
+                    window: std::time::Duration::from_secs(60),
+                    // a delay with a sub-millisecond part:
+                    delay: std::time::Duration::from_micros(1_500),
+                    aggregate: influxdb3_wal::DownsamplingAggregate::Mean,
+                    disabled: false,
+                    watermark_ns: 0,
+                },
+            )],
+        };
+        catalog.apply_catalog_batch(&create)?;
+
+        // replaying the task's creation from the WAL onto the persisted catalog is a no-op:
+        let serialized = serde_json::to_string(&catalog).unwrap();
+        let deserialized: Catalog = serde_json::from_str(&serialized).unwrap();
+        assert!(deserialized.apply_catalog_batch(&create)?.is_none());
+        Ok(())
+    }
 }
@@ -11,14 +11,15 @@ use influxdb3_id::DbId;
 use influxdb3_id::SerdeVecMap;
 use influxdb3_id::TableId;
 use influxdb3_wal::{
-    DistinctCacheDefinition, LastCacheDefinition, LastCacheValueColumnsDef, PluginType,
-    TriggerDefinition, TriggerFlag,
+    DatabaseQuota, DistinctCacheDefinition, DownsamplingAggregate, DownsamplingTaskDefinition,
+    LastCacheDefinition, LastCacheValueColumnsDef, PluginType, TriggerDefinition, TriggerFlag,
 };
 use schema::InfluxColumnType;
 use schema::InfluxFieldType;
 use schema::TIME_DATA_TIMEZONE;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::time::Duration;
 
 impl Serialize for InnerCatalog {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -111,6 +112,10 @@ struct DatabaseSnapshot {
     tables: SerdeVecMap<TableId, TableSnapshot>,
     #[serde(default)]
     processing_engine_triggers: SerdeVecMap<String, ProcessingEngineTriggerSnapshot>,
+    #[serde(default, skip_serializing_if = "is_empty")]
+    downsampling_tasks: SerdeVecMap<String, DownsamplingTaskSnapshot>,
+    #[serde(default, skip_serializing_if = "DatabaseQuota::is_unlimited")]
+    quota: DatabaseQuota,
     deleted: bool,
 }
 
@@ -129,6 +134,15 @@ impl From<&DatabaseSchema> for DatabaseSnapshot {
                 .iter()
                 .map(|(name, trigger)| (name.clone(), trigger.into()))
                 .collect(),
+            downsampling_tasks: {
+                let mut tasks = db.downsampling_tasks.iter().collect::<Vec<_>>();
+                tasks.sort_by(|a, b| a.0.cmp(b.0));
+                tasks
+                    .into_iter()
+                    .map(|(name, task)| (name.clone(), task.into()))
+                    .collect()
+            },
+            quota: db.quota,
             deleted: db.deleted,
         }
     }
@@ -163,6 +177,11 @@ impl From<DatabaseSnapshot> for DatabaseSchema {
                 )
             })
             .collect();
+        let downsampling_tasks = snap
+            .downsampling_tasks
+            .into_iter()
+            .map(|(name, task)| (name, task.into()))
+            .collect();
 
         Self {
             id: snap.id,
@@ -170,6 +189,8 @@ impl From<DatabaseSnapshot> for DatabaseSchema {
             tables,
             table_map,
             processing_engine_triggers,
+            downsampling_tasks,
+            quota: snap.quota,
             deleted: snap.deleted,
         }
     }
@@ -231,6 +252,25 @@ struct ProcessingEngineTriggerSnapshot {
     pub disabled: bool,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct DownsamplingTaskSnapshot {
+    task_name: String,
+    database_name: String,
+    source_table: String,
+    target_database: String,
+    target_table: String,
+    window_seconds: u64,
+    #[serde(default)]
+    delay_ns: u64,
+    aggregate: DownsamplingAggregate,
+    disabled: bool,
+    watermark_ns: i64,
+}
+
+fn is_empty<K: Eq + std::hash::Hash, V>(map: &SerdeVecMap<K, V>) -> bool {
+    map.is_empty()
+}
+
 /// Representation of Arrow's `DataType` for table snapshots.
 ///
 /// Uses `#[non_exhaustive]` with the assumption that variants will be added as we support
@@ -484,6 +524,40 @@ impl From<&TriggerDefinition> for ProcessingEngineTriggerSnapshot {
     }
 }
 
+impl From<&DownsamplingTaskDefinition> for DownsamplingTaskSnapshot {
+    fn from(task: &DownsamplingTaskDefinition) -> Self {
+        Self {
+            task_name: task.task_name.to_string(),
+            database_name: task.database_name.to_string(),
+            source_table: task.source_table.to_string(),
+            target_database: task.target_database.to_string(),
+            target_table: task.target_table.to_string(),
+            window_seconds: task.window.as_secs(),
+            delay_ns: u64::try_from(task.delay.as_nanos()).unwrap_or(u64::MAX),
+            aggregate: task.aggregate,
+            disabled: task.disabled,
+            watermark_ns: task.watermark_ns,
+        }
+    }
+}
+
+impl From<DownsamplingTaskSnapshot> for DownsamplingTaskDefinition {
+    fn from(snap: DownsamplingTaskSnapshot) -> Self {
+        Self {
+            task_name: snap.task_name,
+            database_name: snap.database_name,
+            source_table: snap.source_table,
+            target_database: snap.target_database,
+            target_table: snap.target_table,
+            window: Duration::from_secs(snap.window_seconds),
+            delay: Duration::from_nanos(snap.delay_ns),
+            aggregate: snap.aggregate,
+            disabled: snap.disabled,
+            watermark_ns: snap.watermark_ns,
+        }
+    }
+}
+
 // NOTE: Ideally, we will remove the need for the InfluxFieldType, and be able
 // to use Arrow's DataType directly. If that happens, this conversion will need
 // to support the entirety of Arrow's DataType enum, which is why [`DataType`]
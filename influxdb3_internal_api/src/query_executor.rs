@@ -27,6 +27,8 @@ pub enum QueryExecutorError {
     RetentionPoliciesToRecordBatch(#[source] ArrowError),
     #[error("invokded a method that is not implemented: {0}")]
     MethodNotImplemented(&'static str),
+    #[error("quota exceeded: {0}")]
+    QuotaExceeded(String),
     #[error(transparent)]
     Anyhow(#[from] anyhow::Error),
 }
@@ -4,6 +4,21 @@ use tikv_jemalloc_ctl::{epoch, stats};
 
 use metric::{Attributes, MetricKind, Observation, Reporter};
 
+use crate::HeapStats;
+
+/// Advance the jemalloc epoch and read a fresh set of [`HeapStats`]
+pub(crate) fn read_heap_stats() -> Result<HeapStats, tikv_jemalloc_ctl::Error> {
+    epoch::advance()?;
+    Ok(HeapStats {
+        active: stats::active::read()? as u64,
+        allocated: stats::allocated::read()? as u64,
+        metadata: stats::metadata::read()? as u64,
+        mapped: stats::mapped::read()? as u64,
+        resident: stats::resident::read()? as u64,
+        retained: stats::retained::read()? as u64,
+    })
+}
+
 /// A `metric::Instrument` that reports jemalloc memory statistics, specifically:
 ///
 /// - a u64 gauge called "jemalloc_memstats_bytes"
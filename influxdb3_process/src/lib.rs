@@ -30,6 +30,35 @@ pub fn build_malloc_conf() -> String {
         .to_string()
 }
 
+/// Snapshot of heap allocator statistics, in bytes.
+///
+/// See <http://jemalloc.net/jemalloc.3.html#stats.active> for the meaning of each field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeapStats {
+    pub active: u64,
+    pub allocated: u64,
+    pub metadata: u64,
+    pub mapped: u64,
+    pub resident: u64,
+    pub retained: u64,
+}
+
+/// Read the current heap statistics from the allocator.
+///
+/// Returns `None` if `influxdb3` was not built with jemalloc as its allocator.
+#[cfg(any(not(feature = "jemalloc_replacing_malloc"), target_env = "msvc"))]
+pub fn heap_stats() -> Option<HeapStats> {
+    None
+}
+
+/// Read the current heap statistics from the allocator.
+///
+/// Returns `None` if `influxdb3` was not built with jemalloc as its allocator.
+#[cfg(all(feature = "jemalloc_replacing_malloc", not(target_env = "msvc")))]
+pub fn heap_stats() -> Option<HeapStats> {
+    crate::jemalloc::read_heap_stats().ok()
+}
+
 /// Package version.
 pub static INFLUXDB3_VERSION: LazyLock<&'static str> =
     LazyLock::new(|| option_env!("CARGO_PKG_VERSION").unwrap_or("UNKNOWN"));
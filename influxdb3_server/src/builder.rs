@@ -1,11 +1,14 @@
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
+use crate::downsampling::DownsamplingManager;
+use crate::reload::ConfigReloader;
 use crate::{CommonServerState, Server, auth::DefaultAuthorizer, http::HttpApi};
 use authz::Authorizer;
 use influxdb3_internal_api::query_executor::QueryExecutor;
 use influxdb3_processing_engine::ProcessingEngineManagerImpl;
 use influxdb3_processing_engine::manager::ProcessingEngineManager;
-use influxdb3_write::{WriteBuffer, persister::Persister};
+use influxdb3_write::{WriteBuffer, backup::BackupManager, persister::Persister};
 use iox_time::TimeProvider;
 use tokio::net::TcpListener;
 
@@ -13,7 +16,9 @@ use tokio::net::TcpListener;
 pub struct ServerBuilder<W, Q, P, T, L, E> {
     common_state: CommonServerState,
     time_provider: T,
-    max_request_size: usize,
+    max_request_size: Arc<AtomicUsize>,
+    debug_endpoints_enabled: bool,
+    config_reloader: Option<Arc<dyn ConfigReloader>>,
     write_buffer: W,
     query_executor: Q,
     persister: P,
@@ -36,7 +41,9 @@ impl
         Self {
             common_state,
             time_provider: NoTimeProvider,
-            max_request_size: usize::MAX,
+            max_request_size: Arc::new(AtomicUsize::new(usize::MAX)),
+            debug_endpoints_enabled: false,
+            config_reloader: None,
             write_buffer: NoWriteBuf,
             query_executor: NoQueryExec,
             persister: NoPersister,
@@ -48,8 +55,24 @@ impl
 }
 
 impl<W, Q, P, T, L, E> ServerBuilder<W, Q, P, T, L, E> {
-    pub fn max_request_size(mut self, max_request_size: usize) -> Self {
-        self.max_request_size = max_request_size;
+    pub fn max_request_size(self, max_request_size: usize) -> Self {
+        self.max_request_size
+            .store(max_request_size, Ordering::Relaxed);
+        self
+    }
+
+    /// The maximum request size of the server being built, which can be changed while it runs
+    pub fn max_request_size_handle(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.max_request_size)
+    }
+
+    pub fn config_reloader(mut self, config_reloader: Arc<dyn ConfigReloader>) -> Self {
+        self.config_reloader = Some(config_reloader);
+        self
+    }
+
+    pub fn debug_endpoints_enabled(mut self, enabled: bool) -> Self {
+        self.debug_endpoints_enabled = enabled;
         self
     }
 
@@ -94,6 +117,8 @@ impl<Q, P, T, L, E> ServerBuilder<NoWriteBuf, Q, P, T, L, E> {
             common_state: self.common_state,
             time_provider: self.time_provider,
             max_request_size: self.max_request_size,
+            debug_endpoints_enabled: self.debug_endpoints_enabled,
+            config_reloader: self.config_reloader,
             write_buffer: WithWriteBuf(wb),
             query_executor: self.query_executor,
             persister: self.persister,
@@ -113,6 +138,8 @@ impl<W, P, T, L, E> ServerBuilder<W, NoQueryExec, P, T, L, E> {
             common_state: self.common_state,
             time_provider: self.time_provider,
             max_request_size: self.max_request_size,
+            debug_endpoints_enabled: self.debug_endpoints_enabled,
+            config_reloader: self.config_reloader,
             write_buffer: self.write_buffer,
             query_executor: WithQueryExec(qe),
             persister: self.persister,
@@ -129,6 +156,8 @@ impl<W, Q, T, L, E> ServerBuilder<W, Q, NoPersister, T, L, E> {
             common_state: self.common_state,
             time_provider: self.time_provider,
             max_request_size: self.max_request_size,
+            debug_endpoints_enabled: self.debug_endpoints_enabled,
+            config_reloader: self.config_reloader,
             write_buffer: self.write_buffer,
             query_executor: self.query_executor,
             persister: WithPersister(p),
@@ -145,6 +174,8 @@ impl<W, Q, P, L, E> ServerBuilder<W, Q, P, NoTimeProvider, L, E> {
             common_state: self.common_state,
             time_provider: WithTimeProvider(tp),
             max_request_size: self.max_request_size,
+            debug_endpoints_enabled: self.debug_endpoints_enabled,
+            config_reloader: self.config_reloader,
             write_buffer: self.write_buffer,
             query_executor: self.query_executor,
             persister: self.persister,
@@ -161,6 +192,8 @@ impl<W, Q, P, T, E> ServerBuilder<W, Q, P, T, NoListener, E> {
             common_state: self.common_state,
             time_provider: self.time_provider,
             max_request_size: self.max_request_size,
+            debug_endpoints_enabled: self.debug_endpoints_enabled,
+            config_reloader: self.config_reloader,
             write_buffer: self.write_buffer,
             query_executor: self.query_executor,
             persister: self.persister,
@@ -180,6 +213,8 @@ impl<W, Q, P, T, L> ServerBuilder<W, Q, P, T, L, NoProcessingEngine> {
             common_state: self.common_state,
             time_provider: self.time_provider,
             max_request_size: self.max_request_size,
+            debug_endpoints_enabled: self.debug_endpoints_enabled,
+            config_reloader: self.config_reloader,
             write_buffer: self.write_buffer,
             query_executor: self.query_executor,
             persister: self.persister,
@@ -214,6 +249,17 @@ impl<T: TimeProvider>
             .0
             .wal()
             .add_file_notifier(Arc::clone(&processing_engine) as _);
+        let backup_manager = Arc::new(BackupManager::new(
+            persister.object_store(),
+            persister.node_identifier_prefix(),
+            self.write_buffer.0.wal(),
+            Arc::clone(&self.time_provider.0) as _,
+        ));
+        let downsampling_manager = Arc::new(DownsamplingManager::new(
+            Arc::clone(&self.write_buffer.0),
+            Arc::clone(&self.query_executor.0),
+            Arc::clone(&self.time_provider.0) as _,
+        ));
         let http = Arc::new(HttpApi::new(
             self.common_state.clone(),
             Arc::clone(&self.time_provider.0),
@@ -222,6 +268,10 @@ impl<T: TimeProvider>
             processing_engine,
             self.max_request_size,
             Arc::clone(&authorizer),
+            self.debug_endpoints_enabled,
+            backup_manager,
+            downsampling_manager,
+            self.config_reloader,
         ));
         Server {
             common_state: self.common_state,
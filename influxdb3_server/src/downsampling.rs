@@ -0,0 +1,803 @@
+//! Continuous downsampling of the data in a table into fixed windows of time
+//!
+//! A downsampling task periodically aggregates the windows of its source table that have closed
+//! since it last ran, and writes the results to its target table as line protocol. A window is
+//! only downsampled once the task's delay has passed since it closed, to allow for writes that are
+//! still buffered in the WAL or by clients. The end of the last window written is the task's
+//! watermark; it is recorded in the catalog, so that a task resumes where it left off when the
+//! server restarts. Data that arrives for a window after the watermark has passed it is not
+//! downsampled.
+//!
+//! Each task runs on its own, so that a slow task does not hold up the others. A task that fails
+//! is retried with an exponential backoff. Failures are only tracked in memory.
+
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow::array::{Array, ArrayRef, AsArray, RecordBatch};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Float64Type, Int64Type, UInt64Type};
+use arrow::error::ArrowError;
+use data_types::NamespaceName;
+use datafusion::error::DataFusionError;
+use futures::TryStreamExt;
+use hashbrown::{HashMap, HashSet};
+use influxdb3_catalog::catalog::{Error as CatalogError, InfluxColumnType, InfluxFieldType};
+use influxdb3_internal_api::query_executor::{QueryExecutor, QueryExecutorError};
+use influxdb3_types::http::{DownsamplingTaskCreateRequest, DownsamplingTaskSummary};
+use influxdb3_wal::{
+    CatalogBatch, CatalogOp, DownsamplingAggregate, DownsamplingTaskDefinition,
+    DownsamplingTaskIdentifier, DownsamplingTaskWatermark, WalOp,
+};
+use influxdb3_write::{Precision, WriteBuffer};
+use iox_time::{Time, TimeProvider};
+use observability_deps::tracing::{debug, info, warn};
+use parking_lot::Mutex;
+use schema::TIME_COLUMN_NAME;
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+
+/// How often the manager checks for tasks that have windows to downsample
+pub const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long a task waits after a window closes before downsampling it, unless the task specifies
+/// its own delay; this is well above the default WAL flush interval of one second
+pub const DEFAULT_DELAY: Duration = Duration::from_secs(10);
+
+/// The longest window or delay a task may have, which keeps the arithmetic on windows of
+/// nanosecond timestamps far from overflowing
+const MAX_DURATION: Duration = Duration::from_secs(366 * 24 * 60 * 60);
+
+/// The most windows a task downsamples in one run, to bound the size of the query when a task is
+/// catching up after the server was down
+const MAX_WINDOWS_PER_RUN: i64 = 60;
+
+/// The delay before a failed task is first retried, this doubles with every consecutive failure
+const INITIAL_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Upper bound on the delay before a failed task is retried
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("database not found: {0}")]
+    DatabaseNotFound(String),
+
+    #[error("invalid target database name: {0}")]
+    InvalidTargetDatabase(String),
+
+    #[error(
+        "invalid downsampling window '{0}', it must be a whole number of seconds, between one \
+        second and 366 days"
+    )]
+    InvalidWindow(String),
+
+    #[error("invalid downsampling delay '{0}', it must be at most 366 days")]
+    InvalidDelay(String),
+
+    #[error("a downsampling task cannot write to the table it reads from")]
+    TargetIsSource,
+
+    #[error(transparent)]
+    Catalog(#[from] CatalogError),
+
+    #[error("wal error: {0}")]
+    Wal(#[from] influxdb3_wal::Error),
+
+    #[error("query error: {0}")]
+    Query(#[from] QueryExecutorError),
+
+    #[error("datafusion error: {0}")]
+    DataFusion(#[from] DataFusionError),
+
+    #[error("arrow error: {0}")]
+    Arrow(#[from] ArrowError),
+
+    #[error("error writing downsampled data: {0}")]
+    Write(#[from] influxdb3_write::write_buffer::Error),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The in-memory state of a task that has failed since it last succeeded
+#[derive(Debug, Clone)]
+struct TaskFailures {
+    consecutive_failures: u32,
+    last_error: String,
+    next_attempt: Time,
+}
+
+/// Creates, runs, and tracks the downsampling tasks stored in the catalog
+#[derive(Debug)]
+pub struct DownsamplingManager {
+    write_buffer: Arc<dyn WriteBuffer>,
+    query_executor: Arc<dyn QueryExecutor>,
+    time_provider: Arc<dyn TimeProvider>,
+    failures: Mutex<HashMap<(String, String), TaskFailures>>,
+    running: Mutex<HashSet<(String, String)>>,
+}
+
+impl DownsamplingManager {
+    pub fn new(
+        write_buffer: Arc<dyn WriteBuffer>,
+        query_executor: Arc<dyn QueryExecutor>,
+        time_provider: Arc<dyn TimeProvider>,
+    ) -> Self {
+        Self {
+            write_buffer,
+            query_executor,
+            time_provider,
+            failures: Default::default(),
+            running: Default::default(),
+        }
+    }
+
+    /// Create a task, which starts downsampling from the beginning of the current window
+    pub async fn create_task(
+        &self,
+        request: DownsamplingTaskCreateRequest,
+    ) -> Result<DownsamplingTaskDefinition> {
+        let window = parse_window(&request.window)?;
+        let delay = request
+            .delay
+            .as_deref()
+            .map_or(Ok(DEFAULT_DELAY), parse_delay)?;
+        NamespaceName::new(request.target_db.clone())
+            .map_err(|e| Error::InvalidTargetDatabase(e.to_string()))?;
+        let target_table = request
+            .target_table
+            .unwrap_or_else(|| request.source_table.clone());
+        if request.db == request.target_db && request.source_table == target_table {
+            return Err(Error::TargetIsSource);
+        }
+
+        let now = self.time_provider.now().timestamp_nanos();
+        let task = DownsamplingTaskDefinition {
+            task_name: request.task_name,
+            database_name: request.db,
+            source_table: request.source_table,
+            target_database: request.target_db,
+            target_table,
+            window,
+            delay,
+            aggregate: request.aggregate,
+            disabled: request.disabled,
+            watermark_ns: window_start(now, duration_nanos(window)),
+        };
+        self.apply(
+            &task.database_name,
+            CatalogOp::CreateDownsamplingTask(task.clone()),
+        )
+        .await?;
+        info!(db = %task.database_name, task = %task.task_name, "created downsampling task");
+        Ok(task)
+    }
+
+    pub async fn delete_task(&self, db: &str, task_name: &str) -> Result<()> {
+        self.apply(
+            db,
+            CatalogOp::DeleteDownsamplingTask(identifier(db, task_name)),
+        )
+        .await?;
+        self.failures
+            .lock()
+            .remove(&(db.to_string(), task_name.to_string()));
+        Ok(())
+    }
+
+    pub async fn enable_task(&self, db: &str, task_name: &str) -> Result<()> {
+        self.apply(
+            db,
+            CatalogOp::EnableDownsamplingTask(identifier(db, task_name)),
+        )
+        .await?;
+        // give a re-enabled task a fresh start rather than waiting out its backoff:
+        self.failures
+            .lock()
+            .remove(&(db.to_string(), task_name.to_string()));
+        Ok(())
+    }
+
+    pub async fn disable_task(&self, db: &str, task_name: &str) -> Result<()> {
+        self.apply(
+            db,
+            CatalogOp::DisableDownsamplingTask(identifier(db, task_name)),
+        )
+        .await
+    }
+
+    /// List the tasks in a database along with their state, ordered by name
+    pub fn list_tasks(&self, db: &str) -> Result<Vec<DownsamplingTaskSummary>> {
+        let db_schema = self
+            .write_buffer
+            .catalog()
+            .db_schema(db)
+            .ok_or_else(|| Error::DatabaseNotFound(db.to_string()))?;
+        let failures = self.failures.lock();
+        let mut tasks = db_schema
+            .downsampling_tasks
+            .values()
+            .map(|task| {
+                let failures = failures.get(&(db.to_string(), task.task_name.clone()));
+                DownsamplingTaskSummary {
+                    db: task.database_name.clone(),
+                    task_name: task.task_name.clone(),
+                    source_table: task.source_table.clone(),
+                    target_db: task.target_database.clone(),
+                    target_table: task.target_table.clone(),
+                    window: humantime::format_duration(task.window).to_string(),
+                    delay: humantime::format_duration(task.delay).to_string(),
+                    aggregate: task.aggregate,
+                    disabled: task.disabled,
+                    watermark: task.watermark_ns,
+                    consecutive_failures: failures.map_or(0, |f| f.consecutive_failures),
+                    last_error: failures.map(|f| f.last_error.clone()),
+                    next_attempt: failures.map(|f| f.next_attempt.timestamp_nanos()),
+                }
+            })
+            .collect::<Vec<_>>();
+        tasks.sort_by(|a, b| a.task_name.cmp(&b.task_name));
+        Ok(tasks)
+    }
+
+    /// Start the tasks that have windows to downsample every `interval`, until `shutdown` is
+    /// cancelled. Each task runs in its own tokio task and is not started again while it is still
+    /// running, so a slow task only delays itself. Runs that are still in progress at shutdown are
+    /// abandoned; a run only advances its watermark after writing its windows, so they are
+    /// written again, with the same points, once the server restarts.
+    pub async fn run(self: Arc<Self>, interval: Duration, shutdown: CancellationToken) {
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = interval.tick() => (),
+            }
+            for key in self.pending_tasks() {
+                let Some(running) = RunningTask::start(&self, key) else {
+                    continue;
+                };
+                let shutdown = shutdown.clone();
+                tokio::spawn(async move {
+                    tokio::select! {
+                        _ = shutdown.cancelled() => (),
+                        _ = running.manager.run_tracked(&running.key) => (),
+                    }
+                });
+            }
+        }
+        info!("stopped running downsampling tasks");
+    }
+
+    /// The enabled tasks that are not waiting to be retried after a failure
+    fn pending_tasks(&self) -> Vec<(String, String)> {
+        let now = self.time_provider.now();
+        let failures = self.failures.lock();
+        self.write_buffer
+            .catalog()
+            .active_downsampling_tasks()
+            .into_iter()
+            .filter(|key| failures.get(key).is_none_or(|f| f.next_attempt <= now))
+            .collect()
+    }
+
+    /// Run a task, and track its failures to schedule its retries
+    async fn run_tracked(&self, key: &(String, String)) {
+        let now = self.time_provider.now();
+        match self.run_task(&key.0, &key.1).await {
+            Ok(()) => {
+                self.failures.lock().remove(key);
+            }
+            Err(error) => {
+                let mut failures = self.failures.lock();
+                let consecutive_failures =
+                    failures.get(key).map_or(0, |f| f.consecutive_failures) + 1;
+                let backoff = retry_backoff(consecutive_failures);
+                warn!(
+                    db = %key.0,
+                    task = %key.1,
+                    %error,
+                    consecutive_failures,
+                    ?backoff,
+                    "downsampling task failed"
+                );
+                failures.insert(
+                    key.clone(),
+                    TaskFailures {
+                        consecutive_failures,
+                        last_error: error.to_string(),
+                        next_attempt: now + backoff,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Downsample the windows of a task that have closed since its watermark, and advance the
+    /// watermark past them
+    async fn run_task(&self, db: &str, task_name: &str) -> Result<()> {
+        let catalog = self.write_buffer.catalog();
+        let db_schema = catalog
+            .db_schema(db)
+            .ok_or_else(|| Error::DatabaseNotFound(db.to_string()))?;
+        let Some(task) = db_schema.downsampling_tasks.get(task_name) else {
+            return Ok(());
+        };
+        let end = downsample_until(task, self.time_provider.now().timestamp_nanos());
+        if end <= task.watermark_ns {
+            return Ok(());
+        }
+
+        let query = db_schema
+            .table_definition(task.source_table.as_str())
+            .and_then(|table| {
+                let tags = table
+                    .series_key_names()
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect::<Vec<_>>();
+                let fields = table
+                    .columns
+                    .values()
+                    .filter(|column| aggregate_applies(task.aggregate, column.data_type))
+                    .map(|column| column.name.to_string())
+                    .collect::<Vec<_>>();
+                (!fields.is_empty()).then_some((tags, fields))
+            });
+        if let Some((tags, fields)) = query {
+            let sql = window_query(task, &tags, &fields, task.watermark_ns, end);
+            debug!(%db, task = %task_name, %sql, "running downsampling query");
+            let batches: Vec<RecordBatch> = self
+                .query_executor
+                .query_sql(db, &sql, None, None, None)
+                .await?
+                .try_collect()
+                .await?;
+            let mut lp = String::new();
+            for batch in &batches {
+                write_line_protocol(&task.target_table, &tags, batch, &mut lp)?;
+            }
+            if !lp.is_empty() {
+                let target = NamespaceName::new(task.target_database.clone())
+                    .map_err(|e| Error::InvalidTargetDatabase(e.to_string()))?;
+                self.write_buffer
+                    .write_lp(
+                        target,
+                        &lp,
+                        self.time_provider.now(),
+                        false,
+                        Precision::Nanosecond,
+                        false,
+                    )
+                    .await?;
+            }
+        }
+
+        self.apply(
+            db,
+            CatalogOp::AdvanceDownsamplingTask(DownsamplingTaskWatermark {
+                db_name: db.to_string(),
+                task_name: task_name.to_string(),
+                watermark_ns: end,
+            }),
+        )
+        .await
+    }
+
+    /// Apply an operation on a task to the catalog and persist it in the WAL
+    async fn apply(&self, db: &str, op: CatalogOp) -> Result<()> {
+        let catalog = self.write_buffer.catalog();
+        let (db_id, db_schema) = catalog
+            .db_id_and_schema(db)
+            .ok_or_else(|| Error::DatabaseNotFound(db.to_string()))?;
+        if let Some(catalog_batch) = catalog.apply_catalog_batch(&CatalogBatch {
+            database_id: db_id,
+            database_name: Arc::clone(&db_schema.name),
+            time_ns: self.time_provider.now().timestamp_nanos(),
+            ops: vec![op],
+        })? {
+            self.write_buffer
+                .wal()
+                .write_ops(vec![WalOp::Catalog(catalog_batch)])
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Marks a task as running for as long as it is held, including when the run panics or is
+/// abandoned at shutdown
+struct RunningTask {
+    manager: Arc<DownsamplingManager>,
+    key: (String, String),
+}
+
+impl RunningTask {
+    /// Mark the task as running, or return `None` if it already is
+    fn start(manager: &Arc<DownsamplingManager>, key: (String, String)) -> Option<Self> {
+        manager.running.lock().insert(key.clone()).then(|| Self {
+            manager: Arc::clone(manager),
+            key,
+        })
+    }
+}
+
+impl Drop for RunningTask {
+    fn drop(&mut self) {
+        self.manager.running.lock().remove(&self.key);
+    }
+}
+
+fn identifier(db: &str, task_name: &str) -> DownsamplingTaskIdentifier {
+    DownsamplingTaskIdentifier {
+        db_name: db.to_string(),
+        task_name: task_name.to_string(),
+    }
+}
+
+fn parse_window(window: &str) -> Result<Duration> {
+    humantime::parse_duration(window)
+        .ok()
+        .filter(|w| w.subsec_nanos() == 0 && w.as_secs() > 0 && *w <= MAX_DURATION)
+        .ok_or_else(|| Error::InvalidWindow(window.to_string()))
+}
+
+fn parse_delay(delay: &str) -> Result<Duration> {
+    humantime::parse_duration(delay)
+        .ok()
+        .filter(|d| *d <= MAX_DURATION)
+        .ok_or_else(|| Error::InvalidDelay(delay.to_string()))
+}
+
+/// The nanoseconds in a window or delay, which are bounded by [`MAX_DURATION`] when a task is
+/// created
+fn duration_nanos(duration: Duration) -> i64 {
+    i64::try_from(duration.as_nanos()).unwrap_or(i64::MAX)
+}
+
+/// The start of the window, aligned to the epoch, that contains `time`
+fn window_start(time: i64, window: i64) -> i64 {
+    time - time.rem_euclid(window)
+}
+
+/// The end of the windows that a task can downsample at `now`: those that closed at least the
+/// task's delay ago, up to [`MAX_WINDOWS_PER_RUN`] windows past its watermark
+fn downsample_until(task: &DownsamplingTaskDefinition, now: i64) -> i64 {
+    let window = duration_nanos(task.window);
+    let delay = duration_nanos(task.delay);
+    window_start(now.saturating_sub(delay), window).min(
+        task.watermark_ns
+            .saturating_add(MAX_WINDOWS_PER_RUN.saturating_mul(window)),
+    )
+}
+
+fn retry_backoff(consecutive_failures: u32) -> Duration {
+    INITIAL_RETRY_BACKOFF
+        .saturating_mul(2u32.saturating_pow(consecutive_failures.saturating_sub(1)))
+        .min(MAX_RETRY_BACKOFF)
+}
+
+/// Whether an aggregate can be applied to a column; every field can be counted, but only numeric
+/// fields can be aggregated otherwise
+fn aggregate_applies(aggregate: DownsamplingAggregate, column_type: InfluxColumnType) -> bool {
+    match column_type {
+        InfluxColumnType::Field(
+            InfluxFieldType::Float | InfluxFieldType::Integer | InfluxFieldType::UInteger,
+        ) => true,
+        InfluxColumnType::Field(_) => aggregate == DownsamplingAggregate::Count,
+        InfluxColumnType::Tag | InfluxColumnType::Timestamp => false,
+    }
+}
+
+/// Build the query that aggregates the `fields` of the task's source table into its windows in
+/// `[start, end)`, grouped by the `tags`
+fn window_query(
+    task: &DownsamplingTaskDefinition,
+    tags: &[String],
+    fields: &[String],
+    start: i64,
+    end: i64,
+) -> String {
+    let function = match task.aggregate {
+        DownsamplingAggregate::Mean => "avg",
+        DownsamplingAggregate::Min => "min",
+        DownsamplingAggregate::Max => "max",
+        DownsamplingAggregate::Sum => "sum",
+        DownsamplingAggregate::Count => "count",
+    };
+    let mut columns = vec![format!(
+        "date_bin(INTERVAL '{} seconds', {time}) AS {time}",
+        task.window.as_secs(),
+        time = quote_ident(TIME_COLUMN_NAME),
+    )];
+    columns.extend(tags.iter().map(|tag| quote_ident(tag)));
+    columns.extend(
+        fields
+            .iter()
+            .map(|field| format!("{function}({f}) AS {f}", f = quote_ident(field))),
+    );
+    let group_by = (1..=tags.len() + 1)
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>();
+    format!(
+        "SELECT {columns} FROM {table} WHERE {time} >= '{start}' AND {time} < '{end}' GROUP BY {group_by}",
+        columns = columns.join(", "),
+        table = quote_ident(&task.source_table),
+        time = quote_ident(TIME_COLUMN_NAME),
+        start = Time::from_timestamp_nanos(start).to_rfc3339(),
+        end = Time::from_timestamp_nanos(end).to_rfc3339(),
+        group_by = group_by.join(", "),
+    )
+}
+
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+/// Append the rows of a batch of aggregated windows to `lp` as lines for the `table`; returns the
+/// number of lines written
+fn write_line_protocol(
+    table: &str,
+    tags: &[String],
+    batch: &RecordBatch,
+    lp: &mut String,
+) -> Result<usize> {
+    let schema = batch.schema();
+    let time = cast(
+        batch.column(schema.index_of(TIME_COLUMN_NAME)?),
+        &DataType::Int64,
+    )?;
+    let time = time.as_primitive::<Int64Type>();
+    let mut tag_columns = Vec::with_capacity(tags.len());
+    let mut field_columns = Vec::new();
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        if field.name() == TIME_COLUMN_NAME {
+            continue;
+        }
+        if tags.contains(field.name()) {
+            tag_columns.push((field.name(), cast(column, &DataType::Utf8)?));
+        } else {
+            field_columns.push((field.name(), column));
+        }
+    }
+
+    let mut lines = 0;
+    let mut line = String::new();
+    for row in 0..batch.num_rows() {
+        line.clear();
+        line.push_str(&escape(table, &[',', ' ']));
+        for (name, column) in &tag_columns {
+            let column = column.as_string::<i32>();
+            if column.is_valid(row) && !column.value(row).is_empty() {
+                let _ = write!(
+                    line,
+                    ",{}={}",
+                    escape(name, &[',', '=', ' ']),
+                    escape(column.value(row), &[',', '=', ' '])
+                );
+            }
+        }
+        let mut separator = ' ';
+        for (name, column) in &field_columns {
+            if let Some(value) = field_value(column, row) {
+                let _ = write!(
+                    line,
+                    "{separator}{}={value}",
+                    escape(name, &[',', '=', ' '])
+                );
+                separator = ',';
+            }
+        }
+        // a row with no field values is not a valid line, and would carry no data anyway:
+        if separator == ' ' {
+            continue;
+        }
+        let _ = writeln!(line, " {}", time.value(row));
+        lp.push_str(&line);
+        lines += 1;
+    }
+    Ok(lines)
+}
+
+/// Format an aggregated field value as line protocol, omitting nulls and the non-finite floats
+/// that line protocol cannot represent
+fn field_value(column: &ArrayRef, row: usize) -> Option<String> {
+    if column.is_null(row) {
+        return None;
+    }
+    match column.data_type() {
+        DataType::Float64 => {
+            let value = column.as_primitive::<Float64Type>().value(row);
+            value.is_finite().then(|| value.to_string())
+        }
+        DataType::Int64 => Some(format!(
+            "{}i",
+            column.as_primitive::<Int64Type>().value(row)
+        )),
+        DataType::UInt64 => Some(format!(
+            "{}u",
+            column.as_primitive::<UInt64Type>().value(row)
+        )),
+        _ => None,
+    }
+}
+
+fn escape(value: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c == '\\' || special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{DictionaryArray, Float64Array, Int64Array, TimestampNanosecondArray};
+    use arrow::datatypes::Int32Type;
+
+    use super::*;
+
+    fn task(aggregate: DownsamplingAggregate) -> DownsamplingTaskDefinition {
+        DownsamplingTaskDefinition {
+            task_name: "cpu_1m".to_string(),
+            database_name: "foo".to_string(),
+            source_table: "cpu".to_string(),
+            target_database: "foo_1m".to_string(),
+            target_table: "cpu".to_string(),
+            window: Duration::from_secs(60),
+            delay: Duration::from_secs(10),
+            aggregate,
+            disabled: false,
+            watermark_ns: 0,
+        }
+    }
+
+    #[test]
+    fn windows_are_aligned_to_the_epoch() {
+        let minute = 60_000_000_000;
+        assert_eq!(0, window_start(59_999_999_999, minute));
+        assert_eq!(minute, window_start(minute, minute));
+        assert_eq!(-minute, window_start(-1, minute));
+    }
+
+    #[test]
+    fn windows_are_downsampled_after_the_delay() {
+        let second = 1_000_000_000;
+        let task = task(DownsamplingAggregate::Mean);
+        // the first window closes at one minute, but data may still arrive for ten seconds:
+        assert_eq!(0, downsample_until(&task, 60 * second));
+        assert_eq!(0, downsample_until(&task, 70 * second - 1));
+        assert_eq!(60 * second, downsample_until(&task, 70 * second));
+        let no_delay = DownsamplingTaskDefinition {
+            delay: Duration::ZERO,
+            ..task.clone()
+        };
+        assert_eq!(60 * second, downsample_until(&no_delay, 60 * second));
+        // a task catching up only downsamples a bounded number of windows at a time:
+        assert_eq!(
+            MAX_WINDOWS_PER_RUN * 60 * second,
+            downsample_until(&task, 1_000_000 * second)
+        );
+    }
+
+    #[test]
+    fn windows_and_delays_are_bounded() {
+        assert_eq!(Duration::from_secs(60), parse_window("1m").unwrap());
+        assert_eq!(MAX_DURATION, parse_window("366days").unwrap());
+        for window in ["0s", "1500ms", "367days", "200years", "soon"] {
+            assert!(
+                matches!(parse_window(window), Err(Error::InvalidWindow(_))),
+                "{window}"
+            );
+        }
+        assert_eq!(Duration::from_micros(1_500), parse_delay("1500us").unwrap());
+        for delay in ["367days", "200years", "later"] {
+            assert!(
+                matches!(parse_delay(delay), Err(Error::InvalidDelay(_))),
+                "{delay}"
+            );
+        }
+
+        // the longest windows and delays do not overflow, even far from the epoch:
+        let task = DownsamplingTaskDefinition {
+            window: MAX_DURATION,
+            delay: MAX_DURATION,
+            watermark_ns: i64::MAX - 1,
+            ..task(DownsamplingAggregate::Mean)
+        };
+        downsample_until(&task, i64::MAX);
+        downsample_until(&task, 0);
+    }
+
+    #[test]
+    fn retry_backoff_doubles_up_to_the_limit() {
+        assert_eq!(Duration::from_secs(1), retry_backoff(1));
+        assert_eq!(Duration::from_secs(2), retry_backoff(2));
+        assert_eq!(Duration::from_secs(256), retry_backoff(9));
+        assert_eq!(MAX_RETRY_BACKOFF, retry_backoff(10));
+        assert_eq!(MAX_RETRY_BACKOFF, retry_backoff(u32::MAX));
+    }
+
+    #[test]
+    fn aggregates_apply_to_field_types() {
+        let float = InfluxColumnType::Field(InfluxFieldType::Float);
+        let string = InfluxColumnType::Field(InfluxFieldType::String);
+        assert!(aggregate_applies(DownsamplingAggregate::Mean, float));
+        assert!(!aggregate_applies(DownsamplingAggregate::Mean, string));
+        assert!(aggregate_applies(DownsamplingAggregate::Count, string));
+        assert!(!aggregate_applies(
+            DownsamplingAggregate::Count,
+            InfluxColumnType::Tag
+        ));
+    }
+
+    #[test]
+    fn window_query_groups_by_window_and_tags() {
+        let sql = window_query(
+            &task(DownsamplingAggregate::Mean),
+            &["region".to_string(), "host".to_string()],
+            &["usage".to_string(), "odd\"name".to_string()],
+            0,
+            120_000_000_000,
+        );
+        assert_eq!(
+            "SELECT date_bin(INTERVAL '60 seconds', \"time\") AS \"time\", \"region\", \"host\", \
+            avg(\"usage\") AS \"usage\", avg(\"odd\"\"name\") AS \"odd\"\"name\" FROM \"cpu\" \
+            WHERE \"time\" >= '1970-01-01T00:00:00+00:00' AND \"time\" < '1970-01-01T00:02:00+00:00' \
+            GROUP BY 1, 2, 3",
+            sql
+        );
+    }
+
+    #[test]
+    fn line_protocol_from_windows() {
+        let region: DictionaryArray<Int32Type> = vec![Some("us east"), None, Some("eu,west")]
+            .into_iter()
+            .collect();
+        let batch = RecordBatch::try_from_iter([
+            (
+                "time",
+                Arc::new(TimestampNanosecondArray::from(vec![0, 60, 120])) as ArrayRef,
+            ),
+            ("region", Arc::new(region) as ArrayRef),
+            (
+                "usage",
+                Arc::new(Float64Array::from(vec![Some(0.5), Some(f64::NAN), None])) as ArrayRef,
+            ),
+            (
+                "count",
+                Arc::new(Int64Array::from(vec![Some(2), None, None])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+
+        let mut lp = String::new();
+        let lines =
+            write_line_protocol("cpu 1m", &["region".to_string()], &batch, &mut lp).unwrap();
+        // the second row has no finite field values, and the third has none at all:
+        assert_eq!(1, lines);
+        assert_eq!("cpu\\ 1m,region=us\\ east usage=0.5,count=2i 0\n", lp);
+
+        let mut lp = String::new();
+        let batch = batch.project(&[0, 1, 3]).unwrap();
+        let count = Int64Array::from(vec![1, 2, 3]);
+        let batch = RecordBatch::try_new(
+            batch.schema(),
+            vec![
+                Arc::clone(batch.column(0)),
+                Arc::clone(batch.column(1)),
+                Arc::new(count),
+            ],
+        )
+        .unwrap();
+        write_line_protocol("cpu", &["region".to_string()], &batch, &mut lp).unwrap();
+        assert_eq!(
+            "cpu,region=us\\ east count=1i 0\ncpu count=2i 60\ncpu,region=eu\\,west count=3i 120\n",
+            lp
+        );
+    }
+}
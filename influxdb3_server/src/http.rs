@@ -1,6 +1,8 @@
 //! HTTP API service implementations for `server`
 
 use crate::CommonServerState;
+use crate::downsampling::{self, DownsamplingManager};
+use crate::reload::{self, ConfigReloader};
 use arrow::record_batch::RecordBatch;
 use arrow::util::pretty;
 use authz::Authorizer;
@@ -28,10 +30,11 @@ use influxdb3_internal_api::query_executor::{QueryExecutor, QueryExecutorError};
 use influxdb3_process::{INFLUXDB3_GIT_HASH_SHORT, INFLUXDB3_VERSION};
 use influxdb3_processing_engine::manager::{ProcessingEngineError, ProcessingEngineManager};
 use influxdb3_types::http::*;
-use influxdb3_wal::TriggerSpecificationDefinition;
+use influxdb3_wal::{DatabaseQuota, TriggerSpecificationDefinition};
 use influxdb3_write::BufferedWriteRequest;
 use influxdb3_write::Precision;
 use influxdb3_write::WriteBuffer;
+use influxdb3_write::backup::{BackupManager, BackupManifest};
 use influxdb3_write::persister::TrackedMemoryArrowWriter;
 use influxdb3_write::write_buffer::Error as WriteBufferError;
 use iox_http::write::single_tenant::SingleTenantRequestUnifier;
@@ -51,12 +54,14 @@ use std::pin::Pin;
 use std::str::Utf8Error;
 use std::string::FromUtf8Error;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::task::Poll;
 use std::time::Duration;
 use thiserror::Error;
 use trace::ctx::SpanContext;
 use unicode_segmentation::UnicodeSegmentation;
 
+mod debug;
 mod v1;
 
 #[derive(Debug, Error)]
@@ -229,6 +234,31 @@ pub enum Error {
 
     #[error(transparent)]
     Influxdb3TypesHttp(#[from] influxdb3_types::http::Error),
+
+    /// The debug endpoints were requested, but not enabled on this server
+    #[error("debug endpoints are not enabled on this server")]
+    DebugEndpointsDisabled,
+
+    /// Collecting or encoding a CPU profile failed
+    #[error("failed to collect cpu profile: {0}")]
+    Profiling(String),
+
+    /// The heap statistics are not available from the allocator in use
+    #[error("heap statistics are not available, the server was not built with jemalloc")]
+    HeapStatsUnavailable,
+
+    /// Neither or both of the parameters for deleting backups were given
+    #[error("exactly one of the query parameters 'backup_sequence_number' and 'keep' is required")]
+    InvalidDeleteBackupParams,
+
+    #[error("backup error: {0}")]
+    Backup(#[from] influxdb3_write::backup::Error),
+
+    #[error("downsampling error: {0}")]
+    Downsampling(#[from] downsampling::Error),
+
+    #[error("config reload error: {0}")]
+    Reload(#[from] reload::Error),
 }
 
 #[derive(Debug, Error)]
@@ -442,10 +472,65 @@ impl Error {
                 .status(StatusCode::BAD_REQUEST)
                 .body(Body::from(self.to_string()))
                 .unwrap(),
+            Self::DebugEndpointsDisabled => Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::from(self.to_string()))
+                .unwrap(),
+            Self::HeapStatsUnavailable => Response::builder()
+                .status(StatusCode::NOT_IMPLEMENTED)
+                .body(Body::from(self.to_string()))
+                .unwrap(),
+            Self::Backup(influxdb3_write::backup::Error::BackupNotFound(_)) => Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::from(self.to_string()))
+                .unwrap(),
+            Self::WriteBuffer(err @ WriteBufferError::QuotaExceeded(_))
+            | Self::Query(err @ QueryExecutorError::QuotaExceeded(_)) => {
+                let err: ErrorMessage<()> = ErrorMessage {
+                    error: err.to_string(),
+                    data: None,
+                };
+                let serialized = serde_json::to_string(&err).unwrap();
+                Response::builder()
+                    .status(StatusCode::TOO_MANY_REQUESTS)
+                    .body(Body::from(serialized))
+                    .unwrap()
+            }
+            Self::Reload(reload::Error::NotEnabled) => Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::from(self.to_string()))
+                .unwrap(),
+            Self::Reload(reload::Error::InvalidConfig(_)) => Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(Body::from(self.to_string()))
+                .unwrap(),
+            Self::Downsampling(
+                downsampling::Error::DatabaseNotFound(_)
+                | downsampling::Error::Catalog(CatalogError::DownsamplingTaskNotFound { .. }),
+            ) => Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::from(self.to_string()))
+                .unwrap(),
+            Self::Downsampling(
+                downsampling::Error::InvalidTargetDatabase(_)
+                | downsampling::Error::InvalidWindow(_)
+                | downsampling::Error::InvalidDelay(_)
+                | downsampling::Error::TargetIsSource,
+            ) => Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(Body::from(self.to_string()))
+                .unwrap(),
+            Self::Downsampling(downsampling::Error::Catalog(
+                CatalogError::DownsamplingTaskExists { .. },
+            )) => Response::builder()
+                .status(StatusCode::CONFLICT)
+                .body(Body::from(self.to_string()))
+                .unwrap(),
             Self::MissingQueryParams
             | Self::MissingQueryV1Params
             | Self::MissingWriteParams
-            | Self::MissingDeleteDatabaseParams => Response::builder()
+            | Self::MissingDeleteDatabaseParams
+            | Self::InvalidDeleteBackupParams => Response::builder()
                 .status(StatusCode::BAD_REQUEST)
                 .body(Body::from(self.to_string()))
                 .unwrap(),
@@ -469,9 +554,13 @@ pub(crate) struct HttpApi<T> {
     processing_engine: Arc<dyn ProcessingEngineManager>,
     time_provider: Arc<T>,
     pub(crate) query_executor: Arc<dyn QueryExecutor>,
-    max_request_bytes: usize,
+    max_request_bytes: Arc<AtomicUsize>,
     authorizer: Arc<dyn Authorizer>,
     legacy_write_param_unifier: SingleTenantRequestUnifier,
+    debug_endpoints_enabled: bool,
+    backup_manager: Arc<BackupManager>,
+    pub(crate) downsampling_manager: Arc<DownsamplingManager>,
+    config_reloader: Option<Arc<dyn ConfigReloader>>,
 }
 
 impl<T> HttpApi<T> {
@@ -481,8 +570,12 @@ impl<T> HttpApi<T> {
         write_buffer: Arc<dyn WriteBuffer>,
         query_executor: Arc<dyn QueryExecutor>,
         processing_engine: Arc<dyn ProcessingEngineManager>,
-        max_request_bytes: usize,
+        max_request_bytes: Arc<AtomicUsize>,
         authorizer: Arc<dyn Authorizer>,
+        debug_endpoints_enabled: bool,
+        backup_manager: Arc<BackupManager>,
+        downsampling_manager: Arc<DownsamplingManager>,
+        config_reloader: Option<Arc<dyn ConfigReloader>>,
     ) -> Self {
         let legacy_write_param_unifier = SingleTenantRequestUnifier::new(Arc::clone(&authorizer));
         Self {
@@ -494,6 +587,10 @@ impl<T> HttpApi<T> {
             authorizer,
             legacy_write_param_unifier,
             processing_engine,
+            debug_endpoints_enabled,
+            backup_manager,
+            downsampling_manager,
+            config_reloader,
         }
     }
 }
@@ -633,14 +730,17 @@ where
             Some(v) => return Err(Error::InvalidContentEncoding(v.to_string())),
         };
 
+        // the limit is read once, so that a request is checked against a single limit even if
+        // the limit is changed by a config reload while the request is being read
+        let max_request_bytes = self.max_request_bytes.load(Ordering::Relaxed);
         let mut payload = req.into_body();
 
         let mut body = BytesMut::new();
         while let Some(chunk) = payload.next().await {
             let chunk = chunk.map_err(Error::ClientHangup)?;
             // limit max size of in-memory payload
-            if (body.len() + chunk.len()) > self.max_request_bytes {
-                return Err(Error::RequestSizeExceeded(self.max_request_bytes));
+            if (body.len() + chunk.len()) > max_request_bytes {
+                return Err(Error::RequestSizeExceeded(max_request_bytes));
             }
             body.extend_from_slice(&chunk);
         }
@@ -661,7 +761,7 @@ where
         // In order to detect if the entire stream ahs been read, or truncated,
         // read an extra byte beyond the limit and check the resulting data
         // length - see the max_request_size_truncation test.
-        let mut decoder = decoder.take(self.max_request_bytes as u64 + 1);
+        let mut decoder = decoder.take(max_request_bytes as u64 + 1);
         let mut decoded_data = Vec::new();
         decoder
             .read_to_end(&mut decoded_data)
@@ -669,8 +769,8 @@ where
 
         // If the length is max_size+1, the body is at least max_size+1 bytes in
         // length, and possibly longer, but truncated.
-        if decoded_data.len() > self.max_request_bytes {
-            return Err(Error::RequestSizeExceeded(self.max_request_bytes));
+        if decoded_data.len() > max_request_bytes {
+            return Err(Error::RequestSizeExceeded(max_request_bytes));
         }
 
         Ok(decoded_data.into())
@@ -1293,6 +1393,183 @@ where
             .unwrap())
     }
 
+    /// Take a backup of the data this server has persisted to object storage
+    async fn create_backup(&self) -> Result<Response<Body>> {
+        let manifest = self.backup_manager.backup().await?;
+        let body = serde_json::to_string(&backup_summary(&manifest))?;
+        Ok(Response::builder()
+            .status(StatusCode::CREATED)
+            .header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(body))?)
+    }
+
+    /// List the backups taken of this server, most recent first
+    async fn list_backups(&self) -> Result<Response<Body>> {
+        let summaries = self
+            .backup_manager
+            .list_backups()
+            .await?
+            .iter()
+            .map(backup_summary)
+            .collect::<Vec<_>>();
+        let body = serde_json::to_string(&summaries)?;
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(body))?)
+    }
+
+    /// Delete either a single backup, or all but a number of the most recent backups, and return
+    /// the backups that were deleted
+    async fn delete_backups(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let query = req.uri().query().unwrap_or("");
+        let BackupDeleteRequest {
+            backup_sequence_number,
+            keep,
+        } = serde_urlencoded::from_str(query)?;
+        let deleted = match (backup_sequence_number, keep) {
+            (Some(n), None) => vec![self.backup_manager.delete_backup(n).await?],
+            (None, Some(keep)) => self.backup_manager.prune_backups(keep).await?,
+            _ => return Err(Error::InvalidDeleteBackupParams),
+        };
+        let summaries = deleted.iter().map(backup_summary).collect::<Vec<_>>();
+        let body = serde_json::to_string(&summaries)?;
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(body))?)
+    }
+
+    async fn create_downsampling_task(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let create_req: DownsamplingTaskCreateRequest = self.read_body_json(req).await?;
+        debug!(db = %create_req.db, task = %create_req.task_name, "create_downsampling_task");
+        self.downsampling_manager.create_task(create_req).await?;
+        Ok(Response::builder()
+            .status(StatusCode::CREATED)
+            .body(Body::empty())?)
+    }
+
+    async fn list_downsampling_tasks(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let query = req.uri().query().unwrap_or("");
+        let DownsamplingTaskListRequest { db } = serde_urlencoded::from_str(query)?;
+        let body = serde_json::to_string(&self.downsampling_manager.list_tasks(&db)?)?;
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(body))?)
+    }
+
+    async fn delete_downsampling_task(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let query = req.uri().query().unwrap_or("");
+        let DownsamplingTaskRequest { db, task_name } = serde_urlencoded::from_str(query)?;
+        self.downsampling_manager
+            .delete_task(&db, &task_name)
+            .await?;
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .body(Body::empty())?)
+    }
+
+    async fn enable_downsampling_task(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let query = req.uri().query().unwrap_or("");
+        let DownsamplingTaskRequest { db, task_name } = serde_urlencoded::from_str(query)?;
+        self.downsampling_manager
+            .enable_task(&db, &task_name)
+            .await?;
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .body(Body::empty())?)
+    }
+
+    async fn disable_downsampling_task(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let query = req.uri().query().unwrap_or("");
+        let DownsamplingTaskRequest { db, task_name } = serde_urlencoded::from_str(query)?;
+        self.downsampling_manager
+            .disable_task(&db, &task_name)
+            .await?;
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .body(Body::empty())?)
+    }
+
+    async fn set_database_quota(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let DatabaseQuotaRequest {
+            db,
+            max_series,
+            max_stored_bytes,
+            max_concurrent_queries,
+            max_query_memory_bytes,
+        } = self.read_body_json(req).await?;
+        let quota = DatabaseQuota {
+            max_series,
+            max_stored_bytes,
+            max_concurrent_queries,
+            max_query_memory_bytes,
+        };
+        debug!(%db, ?quota, "set_database_quota");
+        self.write_buffer.set_database_quota(db, quota).await?;
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .body(Body::empty())?)
+    }
+
+    async fn database_usage(&self, req: Request<Body>) -> Result<Response<Body>> {
+        let query = req.uri().query().unwrap_or("");
+        let DatabaseUsageRequest { db } = serde_urlencoded::from_str(query)?;
+        let catalog = self.write_buffer.catalog();
+        let db_schemas =
+            match db {
+                Some(db) => vec![catalog.db_schema(&db).ok_or_else(|| {
+                    WriteBufferError::DatabaseNotFound {
+                        db_name: db.clone(),
+                    }
+                })?],
+                None => catalog
+                    .list_db_schema()
+                    .into_iter()
+                    .filter(|db_schema| !db_schema.deleted)
+                    .collect(),
+            };
+        let quota_tracker = self.write_buffer.quota_tracker();
+        let summaries = db_schemas
+            .iter()
+            .map(|db_schema| {
+                let quota = db_schema.quota;
+                let usage = quota_tracker.usage_snapshot(db_schema.id);
+                DatabaseUsageSummary {
+                    db: db_schema.name.to_string(),
+                    max_series: quota.max_series,
+                    max_stored_bytes: quota.max_stored_bytes,
+                    max_concurrent_queries: quota.max_concurrent_queries,
+                    max_query_memory_bytes: quota.max_query_memory_bytes,
+                    series: usage.series,
+                    stored_bytes: usage.stored_bytes,
+                    running_queries: usage.running_queries,
+                    query_memory_bytes: usage.query_memory_bytes,
+                    rejected_writes: usage.rejected_writes,
+                    rejected_queries: usage.rejected_queries,
+                }
+            })
+            .collect::<Vec<_>>();
+        let body = serde_json::to_string(&summaries)?;
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(body))?)
+    }
+
+    async fn reload_config(&self) -> Result<Response<Body>> {
+        let reloader = self
+            .config_reloader
+            .as_ref()
+            .ok_or(reload::Error::NotEnabled)?;
+        let body = serde_json::to_string(&reloader.reload().await?)?;
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(body))?)
+    }
+
     async fn create_table(&self, req: Request<Body>) -> Result<Response<Body>> {
         let CreateTableRequest {
             db,
@@ -1343,6 +1620,19 @@ where
     }
 }
 
+fn backup_summary(manifest: &BackupManifest) -> BackupSummary {
+    BackupSummary {
+        backup_sequence_number: manifest.backup_sequence_number,
+        created_at: manifest.created_at,
+        wal_high_water_mark: manifest.wal_high_water_mark.as_u64(),
+        last_snapshot_sequence_number: manifest.last_snapshot_sequence_number.map(|s| s.as_u64()),
+        file_count: manifest.files.len() as u64,
+        total_bytes: manifest.total_bytes(),
+        copied_file_count: manifest.copied_file_count,
+        copied_bytes: manifest.copied_bytes,
+    }
+}
+
 /// Check that the content type is application/json
 fn json_content_type(headers: &HeaderMap) -> bool {
     let content_type = if let Some(content_type) = headers.get(CONTENT_TYPE) {
@@ -1756,6 +2046,8 @@ pub(crate) async fn route_request<T: TimeProvider>(
         (Method::GET, "/health" | "/api/v1/health") => http_server.health(),
         (Method::GET | Method::POST, "/ping") => http_server.ping(),
         (Method::GET, "/metrics") => http_server.handle_metrics(),
+        (Method::GET, "/debug/pprof/profile") => http_server.debug_pprof_profile(req).await,
+        (Method::GET, "/debug/pprof/heap") => http_server.debug_pprof_heap(),
         (Method::GET | Method::POST, path) if path.starts_with("/api/v3/engine/") => {
             let path = path.strip_prefix("/api/v3/engine/").unwrap();
             http_server
@@ -1797,7 +2089,30 @@ pub(crate) async fn route_request<T: TimeProvider>(
         (Method::GET, "/api/v3/configure/database") => http_server.show_databases(req).await,
         (Method::POST, "/api/v3/configure/database") => http_server.create_database(req).await,
         (Method::DELETE, "/api/v3/configure/database") => http_server.delete_database(req).await,
+        (Method::POST, "/api/v3/configure/database/quota") => {
+            http_server.set_database_quota(req).await
+        }
+        (Method::GET, "/api/v3/configure/database/usage") => http_server.database_usage(req).await,
         (Method::POST, "/api/v3/configure/table") => http_server.create_table(req).await,
+        (Method::POST, "/api/v3/configure/reload") => http_server.reload_config().await,
+        (Method::GET, "/api/v3/configure/backup") => http_server.list_backups().await,
+        (Method::POST, "/api/v3/configure/backup") => http_server.create_backup().await,
+        (Method::DELETE, "/api/v3/configure/backup") => http_server.delete_backups(req).await,
+        (Method::GET, "/api/v3/configure/downsampling_task") => {
+            http_server.list_downsampling_tasks(req).await
+        }
+        (Method::POST, "/api/v3/configure/downsampling_task") => {
+            http_server.create_downsampling_task(req).await
+        }
+        (Method::DELETE, "/api/v3/configure/downsampling_task") => {
+            http_server.delete_downsampling_task(req).await
+        }
+        (Method::POST, "/api/v3/configure/downsampling_task/enable") => {
+            http_server.enable_downsampling_task(req).await
+        }
+        (Method::POST, "/api/v3/configure/downsampling_task/disable") => {
+            http_server.disable_downsampling_task(req).await
+        }
         // TODO: make table delete to use path param (DELETE db/foodb/table/bar)
         (Method::DELETE, "/api/v3/configure/table") => http_server.delete_table(req).await,
         (Method::POST, "/api/v3/plugin_test/wal") => {
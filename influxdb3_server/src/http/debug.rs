@@ -0,0 +1,134 @@
+//! Debug endpoints for diagnosing performance issues on a running server
+//!
+//! These are only served when the server is started with `--enable-debug-endpoints`.
+
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Request, Response, StatusCode};
+use iox_time::TimeProvider;
+use observability_deps::tracing::info;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::{Error, HttpApi, Result};
+
+/// Default duration of a CPU profile, in seconds; this matches the Go `pprof` tooling
+const DEFAULT_PROFILE_SECONDS: u64 = 30;
+
+/// Upper bound on the duration of a CPU profile, in seconds
+const MAX_PROFILE_SECONDS: u64 = 300;
+
+/// Default sampling frequency of the CPU profiler, in Hz
+const DEFAULT_PROFILE_FREQUENCY: i32 = 99;
+
+/// Upper bound on the sampling frequency of the CPU profiler, in Hz
+const MAX_PROFILE_FREQUENCY: i32 = 1000;
+
+/// Query parameters accepted by the `/debug/pprof/profile` API
+#[derive(Debug, Default, Deserialize)]
+struct ProfileParams {
+    seconds: Option<u64>,
+    frequency: Option<i32>,
+}
+
+/// Response body of the `/debug/pprof/heap` API
+#[derive(Debug, Serialize)]
+struct HeapSummaryResponse {
+    active_bytes: u64,
+    allocated_bytes: u64,
+    metadata_bytes: u64,
+    mapped_bytes: u64,
+    resident_bytes: u64,
+    retained_bytes: u64,
+}
+
+impl<T> HttpApi<T>
+where
+    T: TimeProvider,
+{
+    /// Collect a CPU profile over the requested number of seconds and return it
+    /// in the protobuf format understood by `go tool pprof`
+    pub(super) async fn debug_pprof_profile(&self, req: Request<Body>) -> Result<Response<Body>> {
+        if !self.debug_endpoints_enabled {
+            return Err(Error::DebugEndpointsDisabled);
+        }
+        let params: ProfileParams = req
+            .uri()
+            .query()
+            .map(serde_urlencoded::from_str)
+            .transpose()?
+            .unwrap_or_default();
+        let seconds = params
+            .seconds
+            .unwrap_or(DEFAULT_PROFILE_SECONDS)
+            .clamp(1, MAX_PROFILE_SECONDS);
+        let frequency = params
+            .frequency
+            .unwrap_or(DEFAULT_PROFILE_FREQUENCY)
+            .clamp(1, MAX_PROFILE_FREQUENCY);
+
+        info!(seconds, frequency, "collecting cpu profile");
+        let body = collect_cpu_profile(Duration::from_secs(seconds), frequency).await?;
+
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .header(
+                "Content-Disposition",
+                "attachment; filename=\"influxdb3.pprof\"",
+            )
+            .body(Body::from(body))
+            .map_err(Into::into)
+    }
+
+    /// Return a JSON summary of the heap allocator statistics
+    pub(super) fn debug_pprof_heap(&self) -> Result<Response<Body>> {
+        if !self.debug_endpoints_enabled {
+            return Err(Error::DebugEndpointsDisabled);
+        }
+        let stats = influxdb3_process::heap_stats().ok_or(Error::HeapStatsUnavailable)?;
+        let body = serde_json::to_string(&HeapSummaryResponse {
+            active_bytes: stats.active,
+            allocated_bytes: stats.allocated,
+            metadata_bytes: stats.metadata,
+            mapped_bytes: stats.mapped,
+            resident_bytes: stats.resident,
+            retained_bytes: stats.retained,
+        })?;
+
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .map_err(Into::into)
+    }
+}
+
+#[cfg(unix)]
+async fn collect_cpu_profile(duration: Duration, frequency: i32) -> Result<Vec<u8>> {
+    use pprof::protos::Message;
+
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(frequency)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()
+        .map_err(|e| Error::Profiling(e.to_string()))?;
+    tokio::time::sleep(duration).await;
+    let profile = guard
+        .report()
+        .build()
+        .and_then(|report| report.pprof())
+        .map_err(|e| Error::Profiling(e.to_string()))?;
+
+    let mut body = Vec::new();
+    profile
+        .encode(&mut body)
+        .map_err(|e| Error::Profiling(e.to_string()))?;
+    Ok(body)
+}
+
+#[cfg(not(unix))]
+async fn collect_cpu_profile(_duration: Duration, _frequency: i32) -> Result<Vec<u8>> {
+    Err(Error::Profiling(
+        "cpu profiling is not supported on this platform".to_string(),
+    ))
+}
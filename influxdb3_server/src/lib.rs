@@ -13,10 +13,12 @@ clippy::future_not_send
 
 pub mod auth;
 pub mod builder;
+pub mod downsampling;
 mod grpc;
 mod http;
 pub mod query_executor;
 mod query_planner;
+pub mod reload;
 mod service;
 mod system_tables;
 
@@ -165,6 +167,12 @@ where
 
     let hybrid_make_service = hybrid(rest_service, grpc_service);
 
+    // downsampling tasks run for as long as the server does:
+    tokio::spawn(
+        Arc::clone(&server.http.downsampling_manager)
+            .run(downsampling::DEFAULT_CHECK_INTERVAL, shutdown.clone()),
+    );
+
     let addr = AddrIncoming::from_listener(server.listener)?;
     let timer_end = Instant::now();
     let startup_time = timer_end.duration_since(startup_timer);
@@ -12,12 +12,15 @@ use datafusion::common::arrow::array::StringArray;
 use datafusion::common::arrow::datatypes::{DataType, Field, Schema as DatafusionSchema};
 use datafusion::datasource::{TableProvider, TableType};
 use datafusion::error::DataFusionError;
-use datafusion::execution::SendableRecordBatchStream;
+use datafusion::execution::runtime_env::RuntimeEnv;
+use datafusion::execution::session_state::SessionStateBuilder;
+use datafusion::execution::{RecordBatchStream, SendableRecordBatchStream};
 use datafusion::logical_expr::TableProviderFilterPushDown;
 use datafusion::physical_plan::ExecutionPlan;
 use datafusion::prelude::Expr;
 use datafusion_util::MemoryStream;
 use datafusion_util::config::DEFAULT_SCHEMA;
+use futures::{Stream, StreamExt};
 use influxdb_influxql_parser::statement::Statement;
 use influxdb3_cache::distinct_cache::{DISTINCT_CACHE_UDTF_NAME, DistinctCacheFunction};
 use influxdb3_cache::last_cache::{LAST_CACHE_UDTF_NAME, LastCacheFunction};
@@ -25,6 +28,7 @@ use influxdb3_catalog::catalog::{Catalog, DatabaseSchema, TableDefinition};
 use influxdb3_internal_api::query_executor::{QueryExecutor, QueryExecutorError};
 use influxdb3_sys_events::SysEventStore;
 use influxdb3_telemetry::store::TelemetryStore;
+use influxdb3_write::quota::{QueryMemoryPool, QueryPermit};
 use influxdb3_write::{ChunkFilter, WriteBuffer};
 use iox_query::QueryDatabase;
 use iox_query::exec::{Executor, IOxSessionContext, QueryConfig};
@@ -41,7 +45,9 @@ use std::any::Any;
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{Context, Poll};
 use tokio::sync::Semaphore;
 use trace::ctx::SpanContext;
 use trace::span::{Span, SpanExt, SpanRecorder};
@@ -110,26 +116,94 @@ impl QueryExecutorImpl {
         }
     }
 
-    async fn get_db_namespace(
+    fn get_db_namespace(
         &self,
         database_name: &str,
         span_ctx: &Option<SpanContext>,
+        query_permit: Option<Arc<QueryPermit>>,
     ) -> Result<Arc<dyn QueryNamespace>, QueryExecutorError> {
-        self.namespace(
-            database_name,
-            span_ctx.child_span("get_db_namespace"),
-            false,
-        )
-        .await
-        .map_err(|_| QueryExecutorError::DatabaseNotFound {
-            db_name: database_name.to_string(),
-        })?
-        .ok_or_else(|| QueryExecutorError::DatabaseNotFound {
-            db_name: database_name.to_string(),
+        let _span_recorder = SpanRecorder::new(span_ctx.child_span("get_db_namespace"));
+        self.database(database_name, query_permit)
+            .map(|db| Arc::new(db) as _)
+    }
+
+    /// The database with the given name, whose queries reserve their memory against the
+    /// `query_permit`, if any
+    fn database(
+        &self,
+        database_name: &str,
+        query_permit: Option<Arc<QueryPermit>>,
+    ) -> Result<Database, QueryExecutorError> {
+        let db_schema = self.catalog.db_schema(database_name).ok_or_else(|| {
+            QueryExecutorError::DatabaseNotFound {
+                db_name: database_name.to_string(),
+            }
+        })?;
+        let system_schema_provider = Arc::new(SystemSchemaProvider::AllSystemSchemaTables(
+            AllSystemSchemaTablesProvider::new(
+                Arc::clone(&db_schema),
+                Arc::clone(&self.query_log),
+                Arc::clone(&self.write_buffer),
+                Arc::clone(&self.sys_events_store),
+            ),
+        ));
+        Ok(Database::new(CreateDatabaseArgs {
+            db_schema,
+            write_buffer: Arc::clone(&self.write_buffer),
+            exec: Arc::clone(&self.exec),
+            datafusion_config: Arc::clone(&self.datafusion_config),
+            query_log: Arc::clone(&self.query_log),
+            system_schema_provider,
+            query_permit,
+        }))
+    }
+
+    /// Admit a query against the database under the database's quota
+    fn start_query(&self, database_name: &str) -> Result<QueryPermit, QueryExecutorError> {
+        let db_schema = self.catalog.db_schema(database_name).ok_or_else(|| {
+            QueryExecutorError::DatabaseNotFound {
+                db_name: database_name.to_string(),
+            }
+        })?;
+        self.write_buffer
+            .quota_tracker()
+            .start_query(&db_schema)
+            .map_err(|e| QueryExecutorError::QuotaExceeded(e.to_string()))
+    }
+}
+
+/// Holds the [`QueryPermit`] of a query until the stream of its results is dropped
+struct QuotaStream {
+    inner: SendableRecordBatchStream,
+    _permit: Arc<QueryPermit>,
+}
+
+impl QuotaStream {
+    fn new(
+        inner: SendableRecordBatchStream,
+        permit: Arc<QueryPermit>,
+    ) -> SendableRecordBatchStream {
+        Box::pin(Self {
+            inner,
+            _permit: permit,
         })
     }
 }
 
+impl Stream for QuotaStream {
+    type Item = Result<RecordBatch, DataFusionError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().inner.poll_next_unpin(cx)
+    }
+}
+
+impl RecordBatchStream for QuotaStream {
+    fn schema(&self) -> SchemaRef {
+        self.inner.schema()
+    }
+}
+
 #[async_trait]
 impl QueryExecutor for QueryExecutorImpl {
     async fn query_sql(
@@ -141,7 +215,8 @@ impl QueryExecutor for QueryExecutorImpl {
         external_span_ctx: Option<RequestLogContext>,
     ) -> Result<SendableRecordBatchStream, QueryExecutorError> {
         info!(%database, %query, ?params, "executing sql query");
-        let db = self.get_db_namespace(database, &span_ctx).await?;
+        let permit = Arc::new(self.start_query(database)?);
+        let db = self.get_db_namespace(database, &span_ctx, Some(Arc::clone(&permit)))?;
         query_database_sql(
             db,
             query,
@@ -151,6 +226,7 @@ impl QueryExecutor for QueryExecutorImpl {
             Arc::clone(&self.telemetry_store),
         )
         .await
+        .map(|stream| QuotaStream::new(stream, permit))
     }
 
     async fn query_influxql(
@@ -163,7 +239,8 @@ impl QueryExecutor for QueryExecutorImpl {
         external_span_ctx: Option<RequestLogContext>,
     ) -> Result<SendableRecordBatchStream, QueryExecutorError> {
         info!(database, query, ?params, "executing influxql query");
-        let db = self.get_db_namespace(database, &span_ctx).await?;
+        let permit = Arc::new(self.start_query(database)?);
+        let db = self.get_db_namespace(database, &span_ctx, Some(Arc::clone(&permit)))?;
         query_database_influxql(
             db,
             query,
@@ -174,6 +251,7 @@ impl QueryExecutor for QueryExecutorImpl {
             Arc::clone(&self.telemetry_store),
         )
         .await
+        .map(|stream| QuotaStream::new(stream, permit))
     }
 
     fn show_databases(
@@ -230,15 +308,7 @@ impl QueryExecutor for QueryExecutorImpl {
 
         let mut rows = Vec::with_capacity(databases.len());
         for database in databases {
-            let db = self
-                .namespace(&database, span_ctx.child_span("get database"), false)
-                .await
-                .map_err(|_| QueryExecutorError::DatabaseNotFound {
-                    db_name: database.to_string(),
-                })?
-                .ok_or_else(|| QueryExecutorError::DatabaseNotFound {
-                    db_name: database.to_string(),
-                })?;
+            let db = self.get_db_namespace(&database, &span_ctx, None)?;
             let duration = db.retention_time_ns();
             let (db_name, rp_name) = split_database_name(&database);
             rows.push(RetentionPolicyRow {
@@ -434,6 +504,9 @@ fn split_database_name(db_name: &str) -> (String, String) {
 // This implementation is for the Flight service
 #[async_trait]
 impl QueryDatabase for QueryExecutorImpl {
+    /// The Flight service plans and runs its query in a context from the returned namespace, and
+    /// holds on to that context until the query's results have been sent, so the query is admitted
+    /// here, and its permit is held by the query context
     async fn namespace(
         &self,
         name: &str,
@@ -443,27 +516,13 @@ impl QueryDatabase for QueryExecutorImpl {
     ) -> Result<Option<Arc<dyn QueryNamespace>>, DataFusionError> {
         let _span_recorder = SpanRecorder::new(span);
 
-        let db_schema = self.catalog.db_schema(name).ok_or_else(|| {
-            DataFusionError::External(Box::new(QueryExecutorError::DatabaseNotFound {
-                db_name: name.into(),
-            }))
-        })?;
-        let system_schema_provider = Arc::new(SystemSchemaProvider::AllSystemSchemaTables(
-            AllSystemSchemaTablesProvider::new(
-                Arc::clone(&db_schema),
-                Arc::clone(&self.query_log),
-                Arc::clone(&self.write_buffer),
-                Arc::clone(&self.sys_events_store),
-            ),
-        ));
-        Ok(Some(Arc::new(Database::new(CreateDatabaseArgs {
-            db_schema,
-            write_buffer: Arc::clone(&self.write_buffer),
-            exec: Arc::clone(&self.exec),
-            datafusion_config: Arc::clone(&self.datafusion_config),
-            query_log: Arc::clone(&self.query_log),
-            system_schema_provider,
-        }))))
+        let permit = self
+            .start_query(name)
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        let db = self
+            .database(name, Some(Arc::new(permit)))
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        Ok(Some(Arc::new(db)))
     }
 
     async fn acquire_semaphore(&self, span: Option<Span>) -> InstrumentedAsyncOwnedSemaphorePermit {
@@ -493,6 +552,9 @@ pub struct Database {
     datafusion_config: Arc<HashMap<String, String>>,
     query_log: Arc<QueryLog>,
     system_schema_provider: Arc<SystemSchemaProvider>,
+    /// The permit of the query that the database is used for, against which the query reserves
+    /// its memory
+    query_permit: Option<Arc<QueryPermit>>,
 }
 
 /// Arguments for [`Database::new`]
@@ -504,6 +566,7 @@ pub struct CreateDatabaseArgs {
     datafusion_config: Arc<HashMap<String, String>>,
     query_log: Arc<QueryLog>,
     system_schema_provider: Arc<SystemSchemaProvider>,
+    query_permit: Option<Arc<QueryPermit>>,
 }
 
 impl Database {
@@ -515,6 +578,7 @@ impl Database {
             datafusion_config,
             query_log,
             system_schema_provider,
+            query_permit,
         }: CreateDatabaseArgs,
     ) -> Self {
         Self {
@@ -524,6 +588,7 @@ impl Database {
             datafusion_config,
             query_log,
             system_schema_provider,
+            query_permit,
         }
     }
 
@@ -535,6 +600,7 @@ impl Database {
             datafusion_config: Arc::clone(&db.datafusion_config),
             query_log: Arc::clone(&db.query_log),
             system_schema_provider: Arc::clone(&db.system_schema_provider),
+            query_permit: db.query_permit.clone(),
         }
     }
 
@@ -593,6 +659,9 @@ impl QueryNamespace for Database {
         }
 
         let ctx = cfg.build();
+        if let Some(permit) = &self.query_permit {
+            use_query_memory_pool(&ctx, permit);
+        }
         ctx.inner().register_udtf(
             LAST_CACHE_UDTF_NAME,
             Arc::new(LastCacheFunction::new(
@@ -620,6 +689,26 @@ impl QueryNamespace for Database {
     }
 }
 
+/// Reserve the memory of the queries run in the given context against the query's permit, and so
+/// its database's limit on query memory, before reserving it from the executor's memory pool
+fn use_query_memory_pool(ctx: &IOxSessionContext, permit: &Arc<QueryPermit>) {
+    let state_ref = ctx.inner().state_ref();
+    let mut state = state_ref.write();
+    let runtime = state.runtime_env();
+    let runtime = Arc::new(RuntimeEnv {
+        memory_pool: Arc::new(QueryMemoryPool::new(
+            Arc::clone(&runtime.memory_pool),
+            Arc::clone(permit),
+        )),
+        disk_manager: Arc::clone(&runtime.disk_manager),
+        cache_manager: Arc::clone(&runtime.cache_manager),
+        object_store_registry: Arc::clone(&runtime.object_store_registry),
+    });
+    *state = SessionStateBuilder::new_from_existing(state.clone())
+        .with_runtime_env(runtime)
+        .build();
+}
+
 impl CatalogProvider for Database {
     fn as_any(&self) -> &dyn Any {
         self as &dyn Any
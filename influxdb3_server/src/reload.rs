@@ -0,0 +1,25 @@
+//! Reloading of the server configuration while the server is running, which is triggered by the
+//! `POST /api/v3/configure/reload` API, or by sending the process a `SIGHUP`.
+
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+use influxdb3_types::http::ConfigReloadResponse;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("configuration reload is not enabled, the server was not started with a config file")]
+    NotEnabled,
+
+    #[error("invalid config file: {0}")]
+    InvalidConfig(String),
+}
+
+/// Reloads the configuration of the server, applying the settings that can be changed without a
+/// restart
+#[async_trait]
+pub trait ConfigReloader: Debug + Send + Sync + 'static {
+    /// Reload the configuration, reporting which settings were changed. No settings are applied
+    /// if the configuration is invalid.
+    async fn reload(&self) -> Result<ConfigReloadResponse, Error>;
+}
@@ -4,7 +4,7 @@ use hyper::HeaderMap;
 use hyper::header::ACCEPT;
 use hyper::http::HeaderValue;
 use influxdb3_cache::distinct_cache::MaxCardinality;
-use influxdb3_wal::TriggerFlag;
+use influxdb3_wal::{DownsamplingAggregate, TriggerFlag};
 use iox_query_params::StatementParams;
 use serde::{Deserialize, Serialize};
 
@@ -226,6 +226,92 @@ pub struct SchedulePluginTestResponse {
     pub errors: Vec<String>,
 }
 
+/// Summary of a backup, as returned by the `POST /api/v3/configure/backup` API, and listed by the
+/// `GET /api/v3/configure/backup` and `DELETE /api/v3/configure/backup` APIs
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct BackupSummary {
+    pub backup_sequence_number: u64,
+    /// When the backup was taken, in nanoseconds since the epoch
+    pub created_at: i64,
+    /// The last WAL file included in the backup
+    pub wal_high_water_mark: u64,
+    /// The most recent snapshot included in the backup
+    pub last_snapshot_sequence_number: Option<u64>,
+    /// The number of files the backup is made of
+    pub file_count: u64,
+    /// The total size of the files the backup is made of
+    pub total_bytes: u64,
+    /// The number of files copied by the backup, i.e., not already captured by a previous backup
+    pub copied_file_count: u64,
+    /// The number of bytes copied by the backup
+    pub copied_bytes: u64,
+}
+
+/// Request definition for the `DELETE /api/v3/configure/backup` API, which deletes either the backup
+/// with the given `backup_sequence_number`, or all but the `keep` most recent backups
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BackupDeleteRequest {
+    pub backup_sequence_number: Option<u64>,
+    pub keep: Option<usize>,
+}
+
+/// Request definition for the `POST /api/v3/configure/downsampling_task` API
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DownsamplingTaskCreateRequest {
+    pub db: String,
+    pub task_name: String,
+    pub source_table: String,
+    pub target_db: String,
+    /// The table to write to in the target database, defaults to the name of the source table
+    pub target_table: Option<String>,
+    /// The width of the windows to aggregate the data into, e.g., `1m`
+    pub window: String,
+    /// How long to wait after a window closes before downsampling it, e.g., `30s`; this should
+    /// allow for the WAL flush interval and any delay in clients sending their writes
+    pub delay: Option<String>,
+    pub aggregate: DownsamplingAggregate,
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+/// Request definition for the `DELETE /api/v3/configure/downsampling_task`,
+/// `POST /api/v3/configure/downsampling_task/enable`, and
+/// `POST /api/v3/configure/downsampling_task/disable` APIs
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DownsamplingTaskRequest {
+    pub db: String,
+    pub task_name: String,
+}
+
+/// Request definition for the `GET /api/v3/configure/downsampling_task` API
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DownsamplingTaskListRequest {
+    pub db: String,
+}
+
+/// A downsampling task and its state, as listed by the `GET /api/v3/configure/downsampling_task`
+/// API
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct DownsamplingTaskSummary {
+    pub db: String,
+    pub task_name: String,
+    pub source_table: String,
+    pub target_db: String,
+    pub target_table: String,
+    pub window: String,
+    pub delay: String,
+    pub aggregate: DownsamplingAggregate,
+    pub disabled: bool,
+    /// The end of the most recent window written to the target table, in nanoseconds since the
+    /// epoch
+    pub watermark: i64,
+    /// The number of times the task has failed since it last succeeded
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    /// When a failed task will next be retried, in nanoseconds since the epoch
+    pub next_attempt: Option<i64>,
+}
+
 /// Request definition for the `GET /api/v3/configure/database` API
 #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
 pub struct ShowDatabasesRequest {
@@ -246,6 +332,68 @@ pub struct DeleteDatabaseRequest {
     pub db: String,
 }
 
+/// Request definition for the `POST /api/v3/configure/database/quota` API, limits that are not
+/// provided are unset
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DatabaseQuotaRequest {
+    pub db: String,
+    pub max_series: Option<u64>,
+    pub max_stored_bytes: Option<u64>,
+    pub max_concurrent_queries: Option<u32>,
+    pub max_query_memory_bytes: Option<u64>,
+}
+
+/// Request definition for the `GET /api/v3/configure/database/usage` API, which lists the usage
+/// of all databases if `db` is not provided
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DatabaseUsageRequest {
+    pub db: Option<String>,
+}
+
+/// The quota and resource usage of a database, as listed by the
+/// `GET /api/v3/configure/database/usage` API
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct DatabaseUsageSummary {
+    pub db: String,
+    pub max_series: Option<u64>,
+    pub max_stored_bytes: Option<u64>,
+    pub max_concurrent_queries: Option<u32>,
+    pub max_query_memory_bytes: Option<u64>,
+    pub series: u64,
+    /// The bytes of parquet persisted for the database since the server started, including the
+    /// files that were loaded on startup
+    pub stored_bytes: u64,
+    pub running_queries: u32,
+    /// The bytes of memory reserved by the queries that are running
+    pub query_memory_bytes: u64,
+    /// The number of writes rejected for exceeding the quota since the server started
+    pub rejected_writes: u64,
+    /// The number of queries rejected for exceeding the quota since the server started
+    pub rejected_queries: u64,
+}
+
+/// Response definition for the `POST /api/v3/configure/reload` API, listing the settings that
+/// differ between the reloaded config file and the running server
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ConfigReloadResponse {
+    /// Settings that were applied to the running server
+    pub applied: Vec<ConfigSettingChange>,
+    /// Settings that only take effect once the server is restarted
+    pub requires_restart: Vec<ConfigSettingChange>,
+    /// Settings that are not applied because they are also given on the command line or in the
+    /// environment, which take precedence over the config file
+    pub overridden: Vec<ConfigSettingChange>,
+}
+
+/// A setting that was changed in the config file, a value of `None` means the setting is not in
+/// the file, and the default is used
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ConfigSettingChange {
+    pub setting: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
 /// Request definition for the `POST /api/v3/configure/table` API
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CreateTableRequest {
@@ -345,6 +345,12 @@ pub enum CatalogOp {
     DeleteTrigger(DeleteTriggerDefinition),
     EnableTrigger(TriggerIdentifier),
     DisableTrigger(TriggerIdentifier),
+    CreateDownsamplingTask(DownsamplingTaskDefinition),
+    DeleteDownsamplingTask(DownsamplingTaskIdentifier),
+    EnableDownsamplingTask(DownsamplingTaskIdentifier),
+    DisableDownsamplingTask(DownsamplingTaskIdentifier),
+    AdvanceDownsamplingTask(DownsamplingTaskWatermark),
+    SetDatabaseQuota(DatabaseQuota),
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -353,6 +359,25 @@ pub struct DatabaseDefinition {
     pub database_name: Arc<str>,
 }
 
+/// Limits on the resources that a database may use, each of which is unlimited if not set
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DatabaseQuota {
+    /// The most distinct series the database may hold
+    pub max_series: Option<u64>,
+    /// The most bytes of persisted data the database may hold
+    pub max_stored_bytes: Option<u64>,
+    /// The most queries that may run against the database at once
+    pub max_concurrent_queries: Option<u32>,
+    /// The most bytes of memory that a single query against the database may use while it runs
+    pub max_query_memory_bytes: Option<u64>,
+}
+
+impl DatabaseQuota {
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct DeleteDatabaseDefinition {
     pub database_id: DbId,
@@ -769,6 +794,70 @@ impl TriggerSpecificationDefinition {
     }
 }
 
+/// Defines a task that periodically aggregates the data in a table into fixed windows of time and
+/// writes the results to a target table
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct DownsamplingTaskDefinition {
+    /// The name of the task, is unique within the source database
+    pub task_name: String,
+    /// The name of the database the task reads from
+    pub database_name: String,
+    /// The name of the table the task reads from
+    pub source_table: String,
+    /// The name of the database the task writes to
+    pub target_database: String,
+    /// The name of the table the task writes to
+    pub target_table: String,
+    /// The width of the windows that the data is aggregated into
+    pub window: Duration,
+    /// How long the task waits after a window closes before downsampling it, so that data written
+    /// late, or still buffered in the WAL, is included
+    pub delay: Duration,
+    /// The aggregate applied to each field within a window
+    pub aggregate: DownsamplingAggregate,
+    pub disabled: bool,
+    /// The end of the most recent window that was written to the target table, in nanoseconds
+    /// since the epoch; this starts at the beginning of the window the task was created in
+    pub watermark_ns: i64,
+}
+
+/// The aggregate that a downsampling task applies to the fields in each window
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum DownsamplingAggregate {
+    Mean,
+    Min,
+    Max,
+    Sum,
+    Count,
+}
+
+impl Display for DownsamplingAggregate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mean => write!(f, "mean"),
+            Self::Min => write!(f, "min"),
+            Self::Max => write!(f, "max"),
+            Self::Sum => write!(f, "sum"),
+            Self::Count => write!(f, "count"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct DownsamplingTaskIdentifier {
+    pub db_name: String,
+    pub task_name: String,
+}
+
+/// Records that a downsampling task has written all windows ending at or before `watermark_ns`
+#[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub struct DownsamplingTaskWatermark {
+    pub db_name: String,
+    pub task_name: String,
+    pub watermark_ns: i64,
+}
+
 #[serde_as]
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct WriteBatch {
@@ -0,0 +1,1041 @@
+//! Backup and restore of the data a node has persisted to object storage.
+//!
+//! A backup captures the same set of objects that a server reads when it starts: the most recent
+//! catalog, the snapshot info files and the parquet files they reference, and the WAL files that
+//! have not yet been snapshotted, up to a high-water mark. Together these restore the node to the
+//! point in time at which the high-water mark was taken.
+//!
+//! Backups are written under `{node_id}/backups`. Every object that gets backed up is immutable
+//! once written, so backed up files are shared between backups in `{node_id}/backups/files` and
+//! each backup only copies the files that a previous backup did not already capture. Each backup
+//! then writes a [`BackupManifest`] listing all of the files it is made of.
+//!
+//! Backups are kept until they are deleted, either individually or by keeping only a number of the
+//! most recent backups; see [`BackupManager::delete_backup`] and [`BackupManager::prune_backups`].
+//! Deleting a backup removes its manifest, along with any backed up files that no remaining backup
+//! refers to.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use futures::{StreamExt, TryStreamExt, stream};
+use influxdb3_wal::{SnapshotSequenceNumber, Wal, WalFileSequenceNumber};
+use iox_time::TimeProvider;
+use object_store::path::Path as ObjPath;
+use object_store::{ObjectMeta, ObjectStore};
+use observability_deps::tracing::{info, warn};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+use crate::PersistedSnapshot;
+use crate::paths::{BackupFilePath, BackupManifestFilePath, CatalogFilePath, SnapshotInfoFilePath};
+
+/// The number of times to attempt capturing a consistent set of files for a backup
+const MAX_CAPTURE_ATTEMPTS: usize = 5;
+
+/// The number of objects copied concurrently during a backup or restore
+const COPY_CONCURRENCY: usize = 10;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("object_store error: {0}")]
+    ObjectStore(#[from] object_store::Error),
+
+    #[error("object store path error: {0}")]
+    ObjPath(#[from] object_store::path::Error),
+
+    #[error("serde_json error: {0}")]
+    SerdeJson(#[from] serde_json::Error),
+
+    #[error("no catalog has been persisted for node {0}")]
+    NoCatalog(String),
+
+    #[error("no backups found for node {0}")]
+    NoBackups(String),
+
+    #[error("backup {0} was not found")]
+    BackupNotFound(u64),
+
+    #[error(
+        "cannot restore into node {0}, it already has data in object storage, e.g., {1}. Restore \
+        must be run against a node with no data, and while no server is running for that node"
+    )]
+    RestoreTargetNotEmpty(String, String),
+
+    #[error("unable to capture a consistent set of files after {0} attempts")]
+    Inconsistent(usize),
+
+    #[error("file {0} does not belong to node {1}")]
+    ForeignFile(String, String),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The kind of object that was captured in a backup
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupFileKind {
+    Catalog,
+    Snapshot,
+    Parquet,
+    Wal,
+}
+
+/// A file captured in a backup
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BackupFile {
+    pub kind: BackupFileKind,
+    /// The path of the file, relative to the node identifier prefix
+    pub path: String,
+    pub size_bytes: u64,
+}
+
+/// Describes a backup, and lists all of the files needed to restore it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BackupManifest {
+    pub backup_sequence_number: u64,
+    pub node_id: String,
+    /// When the backup was taken, in nanoseconds since the epoch
+    pub created_at: i64,
+    /// The last WAL file captured in the backup; data written after it is not included
+    pub wal_high_water_mark: WalFileSequenceNumber,
+    /// The most recent snapshot captured in the backup
+    pub last_snapshot_sequence_number: Option<SnapshotSequenceNumber>,
+    /// The number of files this backup copied, i.e., that were not captured by a previous backup
+    pub copied_file_count: u64,
+    /// The number of bytes this backup copied
+    pub copied_bytes: u64,
+    pub files: Vec<BackupFile>,
+}
+
+impl BackupManifest {
+    pub fn total_bytes(&self) -> u64 {
+        self.files.iter().map(|f| f.size_bytes).sum()
+    }
+}
+
+/// Takes backups of the data persisted by this node into its object store
+#[derive(Debug)]
+pub struct BackupManager {
+    object_store: Arc<dyn ObjectStore>,
+    node_identifier_prefix: String,
+    wal: Arc<dyn Wal>,
+    time_provider: Arc<dyn TimeProvider>,
+    /// Only one backup is taken at a time
+    backup_lock: Mutex<()>,
+}
+
+impl BackupManager {
+    pub fn new(
+        object_store: Arc<dyn ObjectStore>,
+        node_identifier_prefix: impl Into<String>,
+        wal: Arc<dyn Wal>,
+        time_provider: Arc<dyn TimeProvider>,
+    ) -> Self {
+        Self {
+            object_store,
+            node_identifier_prefix: node_identifier_prefix.into(),
+            wal,
+            time_provider,
+            backup_lock: Mutex::new(()),
+        }
+    }
+
+    /// Take a backup, copying any files not already captured by a previous backup, and return
+    /// its manifest once it has been written.
+    pub async fn backup(&self) -> Result<BackupManifest> {
+        let _guard = self.backup_lock.lock().await;
+        let prefix = self.node_identifier_prefix.as_str();
+        let backup_sequence_number = latest_manifest(&self.object_store, prefix)
+            .await?
+            .map(|m| m.backup_sequence_number + 1)
+            .unwrap_or_default();
+        let backed_up = list_all(&self.object_store, &BackupFilePath::dir(prefix))
+            .await?
+            .into_iter()
+            .map(|meta| meta.location)
+            .collect::<HashSet<_>>();
+
+        for attempt in 1..=MAX_CAPTURE_ATTEMPTS {
+            let Some(capture) = self.capture().await? else {
+                warn!(
+                    attempt,
+                    "a snapshot completed while capturing backup, retrying"
+                );
+                continue;
+            };
+            let Some((copied_file_count, copied_bytes)) =
+                copy_files(&self.object_store, prefix, &capture.files, &backed_up).await?
+            else {
+                warn!(attempt, "files were removed while copying backup, retrying");
+                continue;
+            };
+            let manifest = BackupManifest {
+                backup_sequence_number,
+                node_id: prefix.to_string(),
+                created_at: self.time_provider.now().timestamp_nanos(),
+                wal_high_water_mark: capture.wal_high_water_mark,
+                last_snapshot_sequence_number: capture.last_snapshot_sequence_number,
+                copied_file_count,
+                copied_bytes,
+                files: capture.files,
+            };
+            let path = BackupManifestFilePath::new(prefix, backup_sequence_number);
+            self.object_store
+                .put(path.as_ref(), serde_json::to_vec_pretty(&manifest)?.into())
+                .await?;
+            info!(
+                backup_sequence_number,
+                wal_high_water_mark = %manifest.wal_high_water_mark,
+                file_count = manifest.files.len(),
+                copied_file_count,
+                copied_bytes,
+                "backup complete"
+            );
+            return Ok(manifest);
+        }
+
+        Err(Error::Inconsistent(MAX_CAPTURE_ATTEMPTS))
+    }
+
+    /// List the manifests of all backups for this node, most recent first
+    pub async fn list_backups(&self) -> Result<Vec<BackupManifest>> {
+        let mut manifests = list_all(
+            &self.object_store,
+            &BackupManifestFilePath::dir(&self.node_identifier_prefix),
+        )
+        .await?;
+        // manifest file names decrease as the backup sequence number increases:
+        manifests.sort_unstable_by(|a, b| a.location.cmp(&b.location));
+        stream::iter(manifests)
+            .map(|meta| get_json(&self.object_store, meta.location))
+            .buffered(COPY_CONCURRENCY)
+            .try_collect()
+            .await
+    }
+
+    /// Delete the backup with the given sequence number, and any backed up files that are not
+    /// part of another backup
+    pub async fn delete_backup(&self, backup_sequence_number: u64) -> Result<BackupManifest> {
+        let _guard = self.backup_lock.lock().await;
+        let prefix = self.node_identifier_prefix.as_str();
+        let path = BackupManifestFilePath::new(prefix, backup_sequence_number);
+        let manifest: BackupManifest =
+            match get_json(&self.object_store, path.as_ref().clone()).await {
+                Err(Error::ObjectStore(object_store::Error::NotFound { .. })) => {
+                    return Err(Error::BackupNotFound(backup_sequence_number));
+                }
+                other => other?,
+            };
+        self.object_store.delete(&path).await?;
+        self.remove_unreferenced_files().await?;
+        info!(backup_sequence_number, "deleted backup");
+        Ok(manifest)
+    }
+
+    /// Delete all but the `keep` most recent backups, along with any backed up files that are
+    /// only part of the deleted backups, and return the manifests of the deleted backups
+    pub async fn prune_backups(&self, keep: usize) -> Result<Vec<BackupManifest>> {
+        let _guard = self.backup_lock.lock().await;
+        let pruned = self
+            .list_backups()
+            .await?
+            .into_iter()
+            .skip(keep)
+            .collect::<Vec<_>>();
+        if pruned.is_empty() {
+            return Ok(pruned);
+        }
+        for manifest in &pruned {
+            let path = BackupManifestFilePath::new(
+                &self.node_identifier_prefix,
+                manifest.backup_sequence_number,
+            );
+            self.object_store.delete(&path).await?;
+        }
+        self.remove_unreferenced_files().await?;
+        info!(pruned = pruned.len(), kept = keep, "pruned backups");
+        Ok(pruned)
+    }
+
+    /// Remove the backed up files that are not listed in the manifest of any backup. The
+    /// manifests are removed before their files, so a failure part way through only leaves
+    /// unreferenced files behind, which are removed the next time this is run.
+    async fn remove_unreferenced_files(&self) -> Result<()> {
+        let prefix = self.node_identifier_prefix.as_str();
+        let referenced = self
+            .list_backups()
+            .await?
+            .into_iter()
+            .flat_map(|manifest| manifest.files)
+            .map(|file| {
+                ObjPath::parse(&file.path).map(|relative| {
+                    BackupFilePath::new(prefix, relative.parts())
+                        .as_ref()
+                        .clone()
+                })
+            })
+            .collect::<Result<HashSet<_>, _>>()?;
+        let unreferenced = list_all(&self.object_store, &BackupFilePath::dir(prefix))
+            .await?
+            .into_iter()
+            .map(|meta| meta.location)
+            .filter(|location| !referenced.contains(location));
+        stream::iter(unreferenced)
+            .map(|location| {
+                let object_store = Arc::clone(&self.object_store);
+                async move { object_store.delete(&location).await }
+            })
+            .buffer_unordered(COPY_CONCURRENCY)
+            .try_collect::<()>()
+            .await?;
+        Ok(())
+    }
+
+    /// Determine the set of files that make up a consistent backup of the node's current state.
+    ///
+    /// Returns `None` if a snapshot completed while the files were being listed, since it may
+    /// have removed WAL files that the backup requires, in which case the capture should be
+    /// retried.
+    async fn capture(&self) -> Result<Option<Capture>> {
+        let prefix = self.node_identifier_prefix.as_str();
+        // The high-water mark is taken first, so that all files listed below are at least as new
+        // as the state it describes:
+        let wal_high_water_mark = self.wal.last_wal_sequence_number().await;
+
+        let mut files = Vec::new();
+        let mut snapshot_metas =
+            list_all(&self.object_store, &SnapshotInfoFilePath::dir(prefix)).await?;
+        // snapshot file names decrease as the snapshot sequence number increases:
+        snapshot_metas.sort_unstable_by(|a, b| a.location.cmp(&b.location));
+        let snapshots: Vec<PersistedSnapshot> = stream::iter(&snapshot_metas)
+            .map(|meta| get_json(&self.object_store, meta.location.clone()))
+            .buffered(COPY_CONCURRENCY)
+            .try_collect()
+            .await?;
+        let last_snapshot = snapshots
+            .first()
+            .map(|s| (s.snapshot_sequence_number, s.wal_file_sequence_number));
+        for (meta, snapshot) in snapshot_metas.iter().zip(snapshots) {
+            for parquet_file in snapshot
+                .databases
+                .into_iter()
+                .flat_map(|(_, db)| db.tables)
+                .flat_map(|(_, files)| files)
+            {
+                files.push(BackupFile {
+                    kind: BackupFileKind::Parquet,
+                    path: relative_path(prefix, &ObjPath::parse(&parquet_file.path)?)?,
+                    size_bytes: parquet_file.size_bytes,
+                });
+            }
+            files.push(BackupFile {
+                kind: BackupFileKind::Snapshot,
+                path: relative_path(prefix, &meta.location)?,
+                size_bytes: meta.size as u64,
+            });
+        }
+
+        // The catalog is persisted before the snapshot info file during a snapshot, so listing
+        // it after the snapshots guarantees it is at least as new as the most recent one:
+        let catalog = list_all(&self.object_store, &CatalogFilePath::dir(prefix))
+            .await?
+            .into_iter()
+            .min_by(|a, b| a.location.cmp(&b.location))
+            .ok_or_else(|| Error::NoCatalog(prefix.to_string()))?;
+
+        // All WAL files after the last snapshot, up to the high-water mark, are needed to replay
+        // the data that has not been persisted as parquet yet:
+        let replay_after = last_snapshot.map(|(_, w)| w).unwrap_or_default();
+        let mut wal_files = list_all(&self.object_store, &ObjPath::from(format!("{prefix}/wal")))
+            .await?
+            .into_iter()
+            .filter_map(|meta| {
+                let number = WalFileSequenceNumber::try_from(&meta.location).ok()?;
+                (number > replay_after && number <= wal_high_water_mark).then_some((number, meta))
+            })
+            .collect::<Vec<_>>();
+        wal_files.sort_unstable_by_key(|(number, _)| *number);
+
+        // Once a snapshot completes, the WAL files it covers can be removed. If one completed
+        // since the snapshots were listed, the WAL files listed above may be incomplete:
+        let latest_snapshot = list_all(&self.object_store, &SnapshotInfoFilePath::dir(prefix))
+            .await?
+            .into_iter()
+            .min_by(|a, b| a.location.cmp(&b.location));
+        if latest_snapshot.map(|meta| meta.location)
+            != snapshot_metas.first().map(|meta| meta.location.clone())
+        {
+            return Ok(None);
+        }
+
+        // If the WAL file at the high-water mark is still being written, it is not included:
+        let wal_high_water_mark = wal_files
+            .last()
+            .map(|(number, _)| *number)
+            .unwrap_or(replay_after);
+        for (_, meta) in wal_files {
+            files.push(BackupFile {
+                kind: BackupFileKind::Wal,
+                path: relative_path(prefix, &meta.location)?,
+                size_bytes: meta.size as u64,
+            });
+        }
+
+        files.push(BackupFile {
+            kind: BackupFileKind::Catalog,
+            path: relative_path(prefix, &catalog.location)?,
+            size_bytes: catalog.size as u64,
+        });
+
+        Ok(Some(Capture {
+            wal_high_water_mark,
+            last_snapshot_sequence_number: last_snapshot.map(|(s, _)| s),
+            files,
+        }))
+    }
+}
+
+/// The set of files captured for a backup
+#[derive(Debug)]
+struct Capture {
+    wal_high_water_mark: WalFileSequenceNumber,
+    last_snapshot_sequence_number: Option<SnapshotSequenceNumber>,
+    files: Vec<BackupFile>,
+}
+
+/// Restore a backup of the node with the given `node_identifier_prefix`, copying its files back
+/// into their original locations. If `backup_sequence_number` is not provided, the most recent
+/// backup is restored.
+///
+/// This must not be run while a server is running for the node. The node must not have any data in
+/// object storage other than its backups, so that no stray snapshot or WAL files are mixed in with
+/// the restored ones. The only exception are files that are part of the backup being restored,
+/// since the catalog is restored last; so if a restore fails part way through, it can be run again.
+pub async fn restore(
+    object_store: Arc<dyn ObjectStore>,
+    node_identifier_prefix: &str,
+    backup_sequence_number: Option<u64>,
+) -> Result<BackupManifest> {
+    let prefix = node_identifier_prefix;
+    let manifest = match backup_sequence_number {
+        Some(n) => {
+            let path = BackupManifestFilePath::new(prefix, n);
+            match get_json(&object_store, path.as_ref().clone()).await {
+                Err(Error::ObjectStore(object_store::Error::NotFound { .. })) => {
+                    return Err(Error::BackupNotFound(n));
+                }
+                other => other?,
+            }
+        }
+        None => latest_manifest(&object_store, prefix)
+            .await?
+            .ok_or_else(|| Error::NoBackups(prefix.to_string()))?,
+    };
+
+    let backups = ObjPath::from(format!("{prefix}/backups"));
+    let catalogs = CatalogFilePath::dir(prefix);
+    let restored = manifest
+        .files
+        .iter()
+        .filter(|f| f.kind != BackupFileKind::Catalog)
+        .map(|f| f.path.as_str())
+        .collect::<HashSet<_>>();
+    for meta in list_all(&object_store, &ObjPath::from(prefix)).await? {
+        if meta.location.prefix_matches(&backups) {
+            continue;
+        }
+        let stray = meta.location.prefix_matches(&catalogs)
+            || !restored.contains(relative_path(prefix, &meta.location)?.as_str());
+        if stray {
+            return Err(Error::RestoreTargetNotEmpty(
+                prefix.to_string(),
+                meta.location.to_string(),
+            ));
+        }
+    }
+
+    let (catalog, files): (Vec<_>, Vec<_>) = manifest
+        .files
+        .iter()
+        .partition(|f| f.kind == BackupFileKind::Catalog);
+    for batch in [files, catalog] {
+        stream::iter(batch)
+            .map(|file| {
+                let object_store = Arc::clone(&object_store);
+                async move {
+                    let relative = ObjPath::parse(&file.path)?;
+                    let from = BackupFilePath::new(prefix, relative.parts());
+                    let to: ObjPath = ObjPath::from(prefix)
+                        .parts()
+                        .chain(relative.parts())
+                        .collect();
+                    object_store.copy(&from, &to).await?;
+                    Ok::<_, Error>(())
+                }
+            })
+            .buffer_unordered(COPY_CONCURRENCY)
+            .try_collect::<()>()
+            .await?;
+    }
+
+    info!(
+        node_id = prefix,
+        backup_sequence_number = manifest.backup_sequence_number,
+        wal_high_water_mark = %manifest.wal_high_water_mark,
+        file_count = manifest.files.len(),
+        "restored backup"
+    );
+    Ok(manifest)
+}
+
+/// Copy the given files into the backup area, skipping those already `backed_up`. Returns the
+/// number of files and bytes that were copied, or `None` if a WAL file was removed by a snapshot,
+/// or the catalog was replaced by a newer one, before it could be copied.
+///
+/// The catalog is copied once all of the other files have been, so that a backup is never left
+/// with a catalog and missing the files it depends on.
+async fn copy_files(
+    object_store: &Arc<dyn ObjectStore>,
+    prefix: &str,
+    files: &[BackupFile],
+    backed_up: &HashSet<ObjPath>,
+) -> Result<Option<(u64, u64)>> {
+    let (catalog, files): (Vec<_>, Vec<_>) = files
+        .iter()
+        .partition(|f| f.kind == BackupFileKind::Catalog);
+    let mut copied_files = 0;
+    let mut copied_bytes = 0;
+    for batch in [files, catalog] {
+        let results: Vec<Option<bool>> = stream::iter(&batch)
+            .map(|file| async move {
+                let relative = ObjPath::parse(&file.path)?;
+                let to = BackupFilePath::new(prefix, relative.parts());
+                if backed_up.contains(&*to) {
+                    return Ok::<_, Error>(Some(false));
+                }
+                let from: ObjPath = ObjPath::from(prefix)
+                    .parts()
+                    .chain(relative.parts())
+                    .collect();
+                match object_store.copy(&from, &to).await {
+                    Ok(()) => Ok(Some(true)),
+                    Err(object_store::Error::NotFound { .. })
+                        if matches!(file.kind, BackupFileKind::Wal | BackupFileKind::Catalog) =>
+                    {
+                        Ok(None)
+                    }
+                    Err(e) => Err(Error::from(e)),
+                }
+            })
+            .buffered(COPY_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        for (file, copied) in batch.iter().zip(results) {
+            match copied {
+                None => return Ok(None),
+                Some(false) => (),
+                Some(true) => {
+                    copied_files += 1;
+                    copied_bytes += file.size_bytes;
+                }
+            }
+        }
+    }
+    Ok(Some((copied_files, copied_bytes)))
+}
+
+/// Get the path of `location` relative to the node identifier `prefix`
+fn relative_path(prefix: &str, location: &ObjPath) -> Result<String> {
+    let relative: ObjPath = location
+        .prefix_match(&ObjPath::from(prefix))
+        .ok_or_else(|| Error::ForeignFile(location.to_string(), prefix.to_string()))?
+        .collect();
+    Ok(relative.to_string())
+}
+
+async fn latest_manifest(
+    object_store: &Arc<dyn ObjectStore>,
+    prefix: &str,
+) -> Result<Option<BackupManifest>> {
+    let latest = list_all(object_store, &BackupManifestFilePath::dir(prefix))
+        .await?
+        .into_iter()
+        .min_by(|a, b| a.location.cmp(&b.location));
+    match latest {
+        Some(meta) => get_json(object_store, meta.location).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+async fn list_all(
+    object_store: &Arc<dyn ObjectStore>,
+    prefix: &ObjPath,
+) -> Result<Vec<ObjectMeta>> {
+    Ok(object_store.list(Some(prefix)).try_collect().await?)
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(
+    object_store: &Arc<dyn ObjectStore>,
+    location: ObjPath,
+) -> Result<T> {
+    let bytes = object_store.get(&location).await?.bytes().await?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::ParquetFile;
+    use crate::paths::ParquetFilePath;
+    use crate::persister::Persister;
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use influxdb3_catalog::catalog::{Catalog, CatalogSequenceNumber};
+    use influxdb3_id::{DbId, TableId};
+    use influxdb3_wal::object_store::wal_path;
+    use influxdb3_wal::{SnapshotDetails, WalFileNotifier, WalOp};
+    use iox_time::{MockProvider, Time};
+    use object_store::memory::InMemory;
+    use pretty_assertions::assert_eq;
+    use tokio::sync::{OwnedSemaphorePermit, oneshot};
+
+    const NODE_ID: &str = "test_host";
+
+    /// A WAL that only reports the sequence number of the last file it persisted
+    #[derive(Debug)]
+    struct TestWal(WalFileSequenceNumber);
+
+    #[async_trait]
+    impl Wal for TestWal {
+        async fn write_ops_unconfirmed(&self, _op: Vec<WalOp>) -> influxdb3_wal::Result<()> {
+            unimplemented!()
+        }
+
+        async fn write_ops(&self, _ops: Vec<WalOp>) -> influxdb3_wal::Result<()> {
+            unimplemented!()
+        }
+
+        async fn flush_buffer(
+            &self,
+        ) -> Option<(
+            oneshot::Receiver<SnapshotDetails>,
+            SnapshotDetails,
+            OwnedSemaphorePermit,
+        )> {
+            unimplemented!()
+        }
+
+        async fn force_flush_buffer(
+            &self,
+        ) -> Option<(
+            oneshot::Receiver<SnapshotDetails>,
+            SnapshotDetails,
+            OwnedSemaphorePermit,
+        )> {
+            unimplemented!()
+        }
+
+        async fn cleanup_snapshot(
+            &self,
+            _snapshot_details: SnapshotDetails,
+            _snapshot_permit: OwnedSemaphorePermit,
+        ) {
+            unimplemented!()
+        }
+
+        async fn last_wal_sequence_number(&self) -> WalFileSequenceNumber {
+            self.0
+        }
+
+        async fn last_snapshot_sequence_number(&self) -> SnapshotSequenceNumber {
+            unimplemented!()
+        }
+
+        async fn shutdown(&self) {}
+
+        fn add_file_notifier(&self, _notifier: Arc<dyn WalFileNotifier>) {
+            unimplemented!()
+        }
+    }
+
+    struct TestNode {
+        object_store: Arc<dyn ObjectStore>,
+        persister: Persister,
+        catalog: Catalog,
+    }
+
+    impl TestNode {
+        async fn new() -> Self {
+            let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+            let time_provider = Arc::new(MockProvider::new(Time::from_timestamp_nanos(0)));
+            let persister = Persister::new(Arc::clone(&object_store), NODE_ID, time_provider);
+            let catalog = Catalog::new(Arc::from(NODE_ID), Arc::from("test_instance"));
+            persister.persist_catalog(&catalog).await.unwrap();
+            Self {
+                object_store,
+                persister,
+                catalog,
+            }
+        }
+
+        fn backup_manager(&self, last_wal_sequence_number: u64) -> BackupManager {
+            BackupManager::new(
+                Arc::clone(&self.object_store),
+                NODE_ID,
+                Arc::new(TestWal(WalFileSequenceNumber::new(
+                    last_wal_sequence_number,
+                ))),
+                Arc::new(MockProvider::new(Time::from_timestamp_nanos(1_000))),
+            )
+        }
+
+        /// Persist a snapshot with a single parquet file, along with a new catalog
+        async fn persist_snapshot(&self, snapshot_sequence_number: u64, wal_file_number: u64) {
+            let wal_file_number = WalFileSequenceNumber::new(wal_file_number);
+            let path = ParquetFilePath::new(NODE_ID, "db", 0, "table", 0, 0, wal_file_number);
+            self.object_store
+                .put(
+                    &path,
+                    Bytes::from(format!("parquet {wal_file_number}")).into(),
+                )
+                .await
+                .unwrap();
+            let _ = self
+                .catalog
+                .db_or_create(&format!("db_{snapshot_sequence_number}"))
+                .unwrap();
+            self.persister.persist_catalog(&self.catalog).await.unwrap();
+
+            let mut snapshot = PersistedSnapshot::new(
+                NODE_ID.to_string(),
+                SnapshotSequenceNumber::new(snapshot_sequence_number),
+                wal_file_number,
+                CatalogSequenceNumber::new(snapshot_sequence_number as u32),
+            );
+            snapshot.add_parquet_file(
+                DbId::from(0),
+                TableId::from(0),
+                ParquetFile::create_for_test(path.to_string()),
+            );
+            self.persister.persist_snapshot(&snapshot).await.unwrap();
+        }
+
+        async fn persist_wal_files(&self, wal_file_numbers: impl IntoIterator<Item = u64>) {
+            for n in wal_file_numbers {
+                self.object_store
+                    .put(
+                        &wal_path(NODE_ID, WalFileSequenceNumber::new(n)),
+                        Bytes::from(format!("wal {n}")).into(),
+                    )
+                    .await
+                    .unwrap();
+            }
+        }
+
+        /// The contents of all objects for the node, excluding backups
+        async fn contents(&self) -> BTreeMap<String, Bytes> {
+            let backups = ObjPath::from(format!("{NODE_ID}/backups"));
+            let mut contents = BTreeMap::new();
+            for meta in list_all(&self.object_store, &ObjPath::from(NODE_ID))
+                .await
+                .unwrap()
+                .into_iter()
+                .filter(|meta| !meta.location.prefix_matches(&backups))
+            {
+                let bytes = self
+                    .object_store
+                    .get(&meta.location)
+                    .await
+                    .unwrap()
+                    .bytes()
+                    .await
+                    .unwrap();
+                contents.insert(meta.location.to_string(), bytes);
+            }
+            contents
+        }
+
+        /// Remove all objects for the node, except for backups
+        async fn remove_all(&self) {
+            for path in self.contents().await.into_keys() {
+                self.object_store
+                    .delete(&ObjPath::from(path))
+                    .await
+                    .unwrap();
+            }
+        }
+    }
+
+    fn kinds(manifest: &BackupManifest) -> Vec<BackupFileKind> {
+        manifest.files.iter().map(|f| f.kind).collect()
+    }
+
+    #[tokio::test]
+    async fn backup_and_restore() {
+        let node = TestNode::new().await;
+        node.persist_wal_files(1..=2).await;
+        node.persist_snapshot(1, 2).await;
+        node.persist_wal_files(3..=4).await;
+
+        // WAL file 4 is past the high-water mark, and WAL files 1 and 2 are covered by the
+        // snapshot, so none of them are captured:
+        let manifest = node.backup_manager(3).backup().await.unwrap();
+        assert_eq!(0, manifest.backup_sequence_number);
+        assert_eq!(WalFileSequenceNumber::new(3), manifest.wal_high_water_mark);
+        assert_eq!(
+            Some(SnapshotSequenceNumber::new(1)),
+            manifest.last_snapshot_sequence_number
+        );
+        assert_eq!(
+            vec![
+                BackupFileKind::Parquet,
+                BackupFileKind::Snapshot,
+                BackupFileKind::Wal,
+                BackupFileKind::Catalog,
+            ],
+            kinds(&manifest)
+        );
+        assert_eq!(4, manifest.copied_file_count);
+        assert_eq!(manifest.total_bytes(), manifest.copied_bytes);
+
+        let mut expected = node.contents().await;
+        for n in [1, 2, 4] {
+            expected.remove(&wal_path(NODE_ID, WalFileSequenceNumber::new(n)).to_string());
+        }
+        // only the most recent catalog is captured:
+        let latest_catalog = format!("{NODE_ID}/{}", manifest.files.last().unwrap().path);
+        expected.retain(|path, _| !path.contains("/catalogs/") || *path == latest_catalog);
+
+        node.remove_all().await;
+        let restored = restore(Arc::clone(&node.object_store), NODE_ID, None)
+            .await
+            .unwrap();
+        assert_eq!(manifest, restored);
+        assert_eq!(expected, node.contents().await);
+    }
+
+    #[tokio::test]
+    async fn backup_is_incremental() {
+        let node = TestNode::new().await;
+        node.persist_wal_files(1..=2).await;
+        node.persist_snapshot(1, 2).await;
+        let backup_manager = node.backup_manager(2);
+        let first = backup_manager.backup().await.unwrap();
+        assert_eq!(3, first.copied_file_count);
+
+        node.persist_wal_files(3..=4).await;
+        node.persist_snapshot(2, 4).await;
+        node.persist_wal_files([5]).await;
+        let backup_manager = node.backup_manager(5);
+        let second = backup_manager.backup().await.unwrap();
+        assert_eq!(1, second.backup_sequence_number);
+        assert_eq!(
+            vec![
+                BackupFileKind::Parquet,
+                BackupFileKind::Snapshot,
+                BackupFileKind::Parquet,
+                BackupFileKind::Snapshot,
+                BackupFileKind::Wal,
+                BackupFileKind::Catalog,
+            ],
+            kinds(&second)
+        );
+        // the first snapshot and its parquet file were already backed up:
+        assert_eq!(4, second.copied_file_count);
+
+        let backups = backup_manager.list_backups().await.unwrap();
+        assert_eq!(vec![second, first], backups);
+    }
+
+    #[tokio::test]
+    async fn restore_specific_backup() {
+        let node = TestNode::new().await;
+        node.persist_snapshot(1, 1).await;
+        let first = node.backup_manager(1).backup().await.unwrap();
+        let expected = node.contents().await;
+        node.persist_snapshot(2, 2).await;
+        node.backup_manager(2).backup().await.unwrap();
+
+        node.remove_all().await;
+        let restored = restore(Arc::clone(&node.object_store), NODE_ID, Some(0))
+            .await
+            .unwrap();
+        assert_eq!(first, restored);
+        assert_eq!(expected, node.contents().await);
+    }
+
+    #[tokio::test]
+    async fn restore_errors() {
+        let node = TestNode::new().await;
+        assert!(matches!(
+            restore(Arc::clone(&node.object_store), NODE_ID, None).await,
+            Err(Error::NoBackups(_))
+        ));
+
+        node.backup_manager(0).backup().await.unwrap();
+        assert!(matches!(
+            restore(Arc::clone(&node.object_store), NODE_ID, Some(42)).await,
+            Err(Error::BackupNotFound(42))
+        ));
+        assert!(matches!(
+            restore(Arc::clone(&node.object_store), NODE_ID, None).await,
+            Err(Error::RestoreTargetNotEmpty(_, path)) if path.contains("/catalogs/")
+        ));
+    }
+
+    #[tokio::test]
+    async fn restore_refuses_stray_files() {
+        let node = TestNode::new().await;
+        node.persist_snapshot(1, 1).await;
+        node.backup_manager(1).backup().await.unwrap();
+        node.remove_all().await;
+
+        // a WAL file that is not part of the backup would be replayed on top of it:
+        node.persist_wal_files([2]).await;
+        let stray = wal_path(NODE_ID, WalFileSequenceNumber::new(2)).to_string();
+        assert!(matches!(
+            restore(Arc::clone(&node.object_store), NODE_ID, None).await,
+            Err(Error::RestoreTargetNotEmpty(_, path)) if path == stray
+        ));
+    }
+
+    #[tokio::test]
+    async fn restore_can_be_rerun() {
+        let node = TestNode::new().await;
+        node.persist_snapshot(1, 1).await;
+        node.backup_manager(1).backup().await.unwrap();
+        let expected = node.contents().await;
+        node.remove_all().await;
+        restore(Arc::clone(&node.object_store), NODE_ID, None)
+            .await
+            .unwrap();
+
+        // simulate a restore that failed before copying the catalog, which is copied last:
+        for path in node.contents().await.into_keys() {
+            if path.contains("/catalogs/") {
+                node.object_store
+                    .delete(&ObjPath::from(path))
+                    .await
+                    .unwrap();
+            }
+        }
+        restore(Arc::clone(&node.object_store), NODE_ID, None)
+            .await
+            .unwrap();
+        assert_eq!(expected, node.contents().await);
+    }
+
+    #[tokio::test]
+    async fn delete_and_prune_backups() {
+        let node = TestNode::new().await;
+        node.persist_snapshot(1, 1).await;
+        let first = node.backup_manager(1).backup().await.unwrap();
+        node.persist_snapshot(2, 2).await;
+        let second = node.backup_manager(2).backup().await.unwrap();
+        node.persist_snapshot(3, 3).await;
+        let backup_manager = node.backup_manager(3);
+        let third = backup_manager.backup().await.unwrap();
+        let backed_up_files = || async {
+            list_all(&node.object_store, &BackupFilePath::dir(NODE_ID))
+                .await
+                .unwrap()
+                .len()
+        };
+        // each backup captures its own catalog, the rest of the files are shared:
+        assert_eq!(third.files.len() + 2, backed_up_files().await);
+
+        // the other files of the first backup are part of the later ones, so only its catalog goes:
+        assert_eq!(first, backup_manager.delete_backup(0).await.unwrap());
+        assert_eq!(third.files.len() + 1, backed_up_files().await);
+        assert!(matches!(
+            backup_manager.delete_backup(0).await,
+            Err(Error::BackupNotFound(0))
+        ));
+
+        assert_eq!(vec![second], backup_manager.prune_backups(1).await.unwrap());
+        assert_eq!(third.files.len(), backed_up_files().await);
+        assert!(backup_manager.prune_backups(1).await.unwrap().is_empty());
+        assert_eq!(
+            vec![third.clone()],
+            backup_manager.list_backups().await.unwrap()
+        );
+
+        // the remaining backup can still be restored:
+        let expected = node.contents().await;
+        node.remove_all().await;
+        let restored = restore(Arc::clone(&node.object_store), NODE_ID, None)
+            .await
+            .unwrap();
+        assert_eq!(third, restored);
+        assert_eq!(expected, node.contents().await);
+    }
+
+    #[tokio::test]
+    async fn copy_is_retried_when_files_are_removed() {
+        let node = TestNode::new().await;
+        node.persist_wal_files(1..=2).await;
+        let backup_manager = node.backup_manager(2);
+        let capture = backup_manager.capture().await.unwrap().unwrap();
+        let backed_up = |file: &BackupFile| {
+            let object_store = Arc::clone(&node.object_store);
+            let path = BackupFilePath::new(NODE_ID, ObjPath::parse(&file.path).unwrap().parts());
+            async move { object_store.head(&path).await.is_ok() }
+        };
+        let catalog = capture
+            .files
+            .iter()
+            .find(|f| f.kind == BackupFileKind::Catalog)
+            .unwrap();
+
+        // the catalog is not copied when a WAL file it depends on was removed:
+        node.object_store
+            .delete(&wal_path(NODE_ID, WalFileSequenceNumber::new(2)))
+            .await
+            .unwrap();
+        assert_eq!(
+            None,
+            copy_files(&node.object_store, NODE_ID, &capture.files, &HashSet::new())
+                .await
+                .unwrap()
+        );
+        assert!(!backed_up(catalog).await);
+
+        // a catalog that was replaced by a newer one is retried:
+        node.persist_wal_files(2..=2).await;
+        let catalog_path = ObjPath::from(format!("{NODE_ID}/{}", catalog.path));
+        node.object_store.delete(&catalog_path).await.unwrap();
+        assert_eq!(
+            None,
+            copy_files(&node.object_store, NODE_ID, &capture.files, &HashSet::new())
+                .await
+                .unwrap()
+        );
+        assert!(!backed_up(catalog).await);
+
+        // and the backup succeeds once the capture is retried:
+        node.persister.persist_catalog(&node.catalog).await.unwrap();
+        let manifest = backup_manager.backup().await.unwrap();
+        assert_eq!(BackupFileKind::Catalog, *kinds(&manifest).last().unwrap());
+    }
+
+    #[tokio::test]
+    async fn backup_requires_catalog() {
+        let object_store: Arc<dyn ObjectStore> = Arc::new(InMemory::new());
+        let backup_manager = BackupManager::new(
+            object_store,
+            NODE_ID,
+            Arc::new(TestWal(WalFileSequenceNumber::new(0))),
+            Arc::new(MockProvider::new(Time::from_timestamp_nanos(0))),
+        );
+        assert!(matches!(
+            backup_manager.backup().await,
+            Err(Error::NoCatalog(_))
+        ));
+    }
+}
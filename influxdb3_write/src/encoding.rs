@@ -0,0 +1,588 @@
+//! Selection of the Parquet encoding used for each column of a persisted file.
+//!
+//! When the buffer is snapshotted, the sorted and deduplicated data for each table is sampled
+//! to compute the cardinality, run lengths, and value ranges of each column. Those statistics
+//! are used to pick a dictionary, run-length, delta, or plain encoding for the column, and the
+//! decision is recorded on the [`ParquetFile`][crate::ParquetFile] in the snapshot.
+
+use std::collections::{BTreeMap, HashSet};
+
+use arrow::array::{Array, ArrayRef, AsArray, RecordBatch};
+use arrow::compute::cast;
+use arrow::datatypes::{
+    DataType, Int8Type, Int16Type, Int32Type, Int64Type, Schema, UInt8Type, UInt16Type, UInt32Type,
+    UInt64Type,
+};
+use arrow::row::{RowConverter, SortField};
+use observability_deps::tracing::warn;
+use parquet::basic::Encoding;
+use parquet::file::properties::WriterPropertiesBuilder;
+use parquet::schema::types::ColumnPath;
+use serde::{Deserialize, Serialize};
+
+/// The maximum number of rows sampled from each column
+const SAMPLE_ROWS: usize = 10_000;
+
+/// The number of blocks of consecutive rows that the sample is made up of, spread evenly across
+/// the column, so that the sample is not biased towards the start of the sorted data while run
+/// lengths and deltas between consecutive values can still be measured
+const SAMPLE_BLOCKS: usize = 10;
+
+/// The maximum ratio of distinct values to sampled rows for a column to be dictionary encoded
+const MAX_DICTIONARY_CARDINALITY_RATIO: f64 = 0.1;
+
+/// The minimum average run length for a column to be run-length encoded
+const MIN_AVERAGE_RUN_LENGTH: f64 = 8.0;
+
+/// The encoding chosen for a column when persisting it to Parquet
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnEncoding {
+    /// Values are stored once in a dictionary page and referenced by index
+    Dictionary,
+    /// Repeated values are collapsed into runs, using `RLE`; this is only chosen for boolean
+    /// columns
+    RunLength,
+    /// The differences between consecutive values are bit-packed, using `DELTA_BINARY_PACKED`;
+    /// this is only chosen for integer and timestamp columns
+    Delta,
+    /// Values are stored as-is
+    Plain,
+}
+
+/// The [`ColumnEncoding`] chosen for each column of a persisted file, by column name
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct ColumnEncodings(BTreeMap<String, ColumnEncoding>);
+
+impl ColumnEncodings {
+    /// Sample the given batches and choose an encoding for each of their columns
+    ///
+    /// Columns that could not be analyzed are left out, and will be written with the default
+    /// Parquet writer settings.
+    pub fn analyze(batches: &[RecordBatch]) -> Self {
+        let Some(schema) = batches.first().map(|b| b.schema()) else {
+            return Self::default();
+        };
+        let mut encodings = BTreeMap::new();
+        for (i, field) in schema.fields().iter().enumerate() {
+            let columns = batches.iter().map(|b| b.column(i)).collect::<Vec<_>>();
+            match ColumnStats::sample(field.data_type(), &columns) {
+                Ok(Some(stats)) => {
+                    encodings.insert(field.name().to_string(), stats.choose(field.data_type()));
+                }
+                Ok(None) => (),
+                Err(error) => {
+                    warn!(%error, column = %field.name(), "unable to analyze column for encoding")
+                }
+            }
+        }
+        Self(encodings)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Get the encoding chosen for the column with the given name
+    pub fn get(&self, column_name: &str) -> Option<ColumnEncoding> {
+        self.0.get(column_name).copied()
+    }
+
+    /// Apply the chosen encodings to the Parquet writer properties for a file with `schema`
+    pub(crate) fn apply(
+        &self,
+        mut builder: WriterPropertiesBuilder,
+        schema: &Schema,
+    ) -> WriterPropertiesBuilder {
+        for field in schema.fields() {
+            let Some(encoding) = self.get(field.name()) else {
+                continue;
+            };
+            let path = ColumnPath::from(field.name().as_str());
+            builder = match encoding {
+                ColumnEncoding::Dictionary => builder.set_column_dictionary_enabled(path, true),
+                ColumnEncoding::RunLength if *field.data_type() == DataType::Boolean => builder
+                    .set_column_dictionary_enabled(path.clone(), false)
+                    .set_column_encoding(path, Encoding::RLE),
+                ColumnEncoding::Delta if physical_integer_bits(field.data_type()).is_some() => {
+                    builder
+                        .set_column_dictionary_enabled(path.clone(), false)
+                        .set_column_encoding(path, Encoding::DELTA_BINARY_PACKED)
+                }
+                ColumnEncoding::RunLength | ColumnEncoding::Delta => continue,
+                ColumnEncoding::Plain => builder
+                    .set_column_dictionary_enabled(path.clone(), false)
+                    .set_column_encoding(path, Encoding::PLAIN),
+            };
+        }
+        builder
+    }
+}
+
+/// The width in bits of the Parquet physical type that an integer or timestamp column of
+/// `data_type` is stored as, or `None` for other columns
+fn physical_integer_bits(data_type: &DataType) -> Option<u32> {
+    match data_type {
+        DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32 => Some(32),
+        DataType::Int64 | DataType::UInt64 | DataType::Timestamp(_, _) => Some(64),
+        _ => None,
+    }
+}
+
+/// The values of an integer or timestamp column, widened so that they and the differences
+/// between them can not overflow
+fn integer_values(column: &dyn Array) -> Result<Vec<Option<i128>>, arrow::error::ArrowError> {
+    fn widen<T>(array: &arrow::array::PrimitiveArray<T>) -> Vec<Option<i128>>
+    where
+        T: arrow::datatypes::ArrowPrimitiveType,
+        T::Native: Into<i128>,
+    {
+        array.iter().map(|v| v.map(Into::into)).collect()
+    }
+    Ok(match column.data_type() {
+        DataType::Int8 => widen(column.as_primitive::<Int8Type>()),
+        DataType::Int16 => widen(column.as_primitive::<Int16Type>()),
+        DataType::Int32 => widen(column.as_primitive::<Int32Type>()),
+        DataType::Int64 => widen(column.as_primitive::<Int64Type>()),
+        DataType::UInt8 => widen(column.as_primitive::<UInt8Type>()),
+        DataType::UInt16 => widen(column.as_primitive::<UInt16Type>()),
+        DataType::UInt32 => widen(column.as_primitive::<UInt32Type>()),
+        DataType::UInt64 => widen(column.as_primitive::<UInt64Type>()),
+        _ => widen(cast(column, &DataType::Int64)?.as_primitive::<Int64Type>()),
+    })
+}
+
+/// The range of the values of an integer or timestamp column, and of the differences between
+/// consecutive values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ValueRange {
+    min: i128,
+    max: i128,
+    min_delta: i128,
+    max_delta: i128,
+}
+
+impl ValueRange {
+    fn new(value: i128) -> Self {
+        Self {
+            min: value,
+            max: value,
+            min_delta: 0,
+            max_delta: 0,
+        }
+    }
+
+    fn observe(&mut self, value: i128, previous: Option<i128>) {
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        if let Some(previous) = previous {
+            let delta = value - previous;
+            self.min_delta = self.min_delta.min(delta);
+            self.max_delta = self.max_delta.max(delta);
+        }
+    }
+
+    /// Check if the values only increase, or only decrease
+    fn is_monotonic(&self) -> bool {
+        self.min_delta >= 0 || self.max_delta <= 0
+    }
+
+    /// The number of bits needed to bit-pack the differences between consecutive values, which
+    /// `DELTA_BINARY_PACKED` stores relative to the smallest difference
+    fn delta_bits(&self) -> u32 {
+        let spread = self.max_delta - self.min_delta;
+        i128::BITS - spread.leading_zeros()
+    }
+}
+
+/// Statistics gathered from a sample of a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ColumnStats {
+    rows: usize,
+    distinct: usize,
+    runs: usize,
+    /// The range of values, for integer and timestamp columns that have non-null values
+    range: Option<ValueRange>,
+}
+
+impl ColumnStats {
+    /// Sample up to [`SAMPLE_ROWS`] rows of the given column arrays, in [`SAMPLE_BLOCKS`] blocks
+    /// of consecutive rows spread across all of them, returning `None` if the column is empty
+    fn sample(
+        data_type: &DataType,
+        columns: &[&ArrayRef],
+    ) -> Result<Option<Self>, arrow::error::ArrowError> {
+        let total_rows = columns.iter().map(|c| c.len()).sum::<usize>();
+        if total_rows == 0 {
+            return Ok(None);
+        }
+        let blocks = if total_rows <= SAMPLE_ROWS {
+            vec![(0, total_rows)]
+        } else {
+            let block_rows = SAMPLE_ROWS / SAMPLE_BLOCKS;
+            (0..SAMPLE_BLOCKS)
+                .map(|i| {
+                    (
+                        i * (total_rows - block_rows) / (SAMPLE_BLOCKS - 1),
+                        block_rows,
+                    )
+                })
+                .collect()
+        };
+
+        let converter = RowConverter::new(vec![SortField::new(data_type.clone())])?;
+        let is_integer = physical_integer_bits(data_type).is_some();
+        let mut distinct = HashSet::new();
+        let mut rows = 0;
+        let mut runs = 0;
+        let mut range: Option<ValueRange> = None;
+
+        for (start, len) in blocks {
+            // runs and deltas are only measured between consecutive rows within a block, which
+            // may span more than one of the columns:
+            let mut prev: Option<Vec<u8>> = None;
+            let mut prev_value: Option<i128> = None;
+            for column in slice_columns(columns, start, len) {
+                let converted = converter.convert_columns(std::slice::from_ref(&column))?;
+                let values = if is_integer {
+                    Some(integer_values(column.as_ref())?)
+                } else {
+                    None
+                };
+                for (i, row) in converted.iter().enumerate() {
+                    let value = row.as_ref();
+                    if prev.as_deref() != Some(value) {
+                        runs += 1;
+                        prev = Some(value.to_vec());
+                    }
+                    if !distinct.contains(value) {
+                        distinct.insert(value.to_vec());
+                    }
+                    if let Some(Some(v)) = values.as_ref().map(|values| values[i]) {
+                        range
+                            .get_or_insert_with(|| ValueRange::new(v))
+                            .observe(v, prev_value);
+                        prev_value = Some(v);
+                    }
+                    rows += 1;
+                }
+            }
+        }
+
+        Ok(Some(Self {
+            rows,
+            distinct: distinct.len(),
+            runs,
+            range,
+        }))
+    }
+
+    fn cardinality_ratio(&self) -> f64 {
+        self.distinct as f64 / self.rows as f64
+    }
+
+    fn average_run_length(&self) -> f64 {
+        self.rows as f64 / self.runs as f64
+    }
+
+    /// Choose an encoding for a column of `data_type` with these statistics
+    fn choose(&self, data_type: &DataType) -> ColumnEncoding {
+        let long_runs = self.average_run_length() >= MIN_AVERAGE_RUN_LENGTH;
+        if *data_type == DataType::Boolean {
+            // Dictionaries do not apply to booleans:
+            return if long_runs {
+                ColumnEncoding::RunLength
+            } else {
+                ColumnEncoding::Plain
+            };
+        }
+        // Dictionary indices are themselves run-length encoded, so low cardinality columns
+        // benefit from both:
+        if self.cardinality_ratio() <= MAX_DICTIONARY_CARDINALITY_RATIO {
+            return ColumnEncoding::Dictionary;
+        }
+        match (physical_integer_bits(data_type), &self.range) {
+            // Runs encode as zero deltas, and monotonic columns, such as `time`, have deltas
+            // that are small compared to the values; otherwise, delta encoding only helps if the
+            // deltas pack into fewer bits than the values are stored in:
+            (Some(bits), Some(range))
+                if long_runs || range.is_monotonic() || range.delta_bits() < bits =>
+            {
+                ColumnEncoding::Delta
+            }
+            (Some(_), _) => ColumnEncoding::Plain,
+            (None, _) if long_runs => ColumnEncoding::Dictionary,
+            (None, _) => ColumnEncoding::Plain,
+        }
+    }
+}
+
+/// The parts of the `columns`, taken as one column, that are in the `len` rows from `start`
+fn slice_columns(columns: &[&ArrayRef], start: usize, len: usize) -> Vec<ArrayRef> {
+    let mut slices = vec![];
+    let mut offset = 0;
+    let end = start + len;
+    for column in columns {
+        let (column_start, column_end) = (offset, offset + column.len());
+        offset = column_end;
+        if column_end <= start || column_start >= end {
+            continue;
+        }
+        let from = start.max(column_start) - column_start;
+        let to = end.min(column_end) - column_start;
+        slices.push(column.slice(from, to - from));
+    }
+    slices
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{
+        ArrayRef, BooleanArray, DictionaryArray, Float64Array, Int64Array, StringArray,
+        TimestampNanosecondArray,
+    };
+    use arrow::datatypes::Int32Type;
+    use parquet::file::properties::WriterProperties;
+
+    use super::*;
+
+    fn batch(columns: Vec<(&str, ArrayRef)>) -> RecordBatch {
+        RecordBatch::try_from_iter(columns).unwrap()
+    }
+
+    #[test]
+    fn choose_encodings() {
+        let n = 1_000;
+        let tags: DictionaryArray<Int32Type> =
+            (0..n).map(|i| ["us-east", "us-west"][i % 2]).collect();
+        let batch = batch(vec![
+            ("region", Arc::new(tags) as ArrayRef),
+            (
+                "unique",
+                Arc::new(StringArray::from_iter_values(
+                    (0..n).map(|i| format!("value-{i}")),
+                )) as ArrayRef,
+            ),
+            (
+                "status",
+                Arc::new(Int64Array::from_iter_values((0..n as i64).map(|i| i / 8))) as ArrayRef,
+            ),
+            (
+                "usage",
+                Arc::new(Float64Array::from_iter_values(
+                    (0..n).map(|i| i as f64 * 1.5),
+                )) as ArrayRef,
+            ),
+            (
+                "flag",
+                Arc::new(BooleanArray::from_iter((0..n).map(|i| Some(i < 500)))) as ArrayRef,
+            ),
+            (
+                "flappy",
+                Arc::new(BooleanArray::from_iter((0..n).map(|i| Some(i % 2 == 0)))) as ArrayRef,
+            ),
+            (
+                "random",
+                Arc::new(Int64Array::from_iter_values(
+                    (0..n as u64).map(|i| i.wrapping_mul(0x9E37_79B9_7F4A_7C15) as i64),
+                )) as ArrayRef,
+            ),
+            (
+                "time",
+                Arc::new(TimestampNanosecondArray::from_iter_values(
+                    (0..n as i64).map(|i| 1_700_000_000_000_000_000 + i * 1_000_000_007),
+                )) as ArrayRef,
+            ),
+        ]);
+
+        let encodings = ColumnEncodings::analyze(&[batch]);
+        assert_eq!(Some(ColumnEncoding::Dictionary), encodings.get("region"));
+        assert_eq!(Some(ColumnEncoding::Plain), encodings.get("unique"));
+        assert_eq!(Some(ColumnEncoding::Delta), encodings.get("status"));
+        assert_eq!(Some(ColumnEncoding::Plain), encodings.get("usage"));
+        assert_eq!(Some(ColumnEncoding::RunLength), encodings.get("flag"));
+        assert_eq!(Some(ColumnEncoding::Plain), encodings.get("flappy"));
+        assert_eq!(Some(ColumnEncoding::Plain), encodings.get("random"));
+        assert_eq!(Some(ColumnEncoding::Delta), encodings.get("time"));
+    }
+
+    #[test]
+    fn sample_spans_batches() {
+        // Each batch on its own has a single run, but across batches the value changes:
+        let batches: Vec<RecordBatch> = (0..4)
+            .map(|i| {
+                batch(vec![(
+                    "a",
+                    Arc::new(Int64Array::from_iter_values(std::iter::repeat_n(i, 5))) as ArrayRef,
+                )])
+            })
+            .collect();
+        let columns = batches.iter().map(|b| b.column(0)).collect::<Vec<_>>();
+        let stats = ColumnStats::sample(&DataType::Int64, &columns)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            ColumnStats {
+                rows: 20,
+                distinct: 4,
+                runs: 4,
+                range: Some(ValueRange {
+                    min: 0,
+                    max: 3,
+                    min_delta: 0,
+                    max_delta: 1,
+                }),
+            },
+            stats
+        );
+    }
+
+    #[test]
+    fn sample_is_bounded() {
+        let batch = batch(vec![(
+            "a",
+            Arc::new(Int64Array::from_iter_values(0..(2 * SAMPLE_ROWS as i64))) as ArrayRef,
+        )]);
+        let stats = ColumnStats::sample(&DataType::Int64, &[batch.column(0)])
+            .unwrap()
+            .unwrap();
+        assert_eq!(SAMPLE_ROWS, stats.rows);
+    }
+
+    #[test]
+    fn sample_is_spread_across_column() {
+        // the values change only after the first SAMPLE_ROWS rows, across two batches:
+        let batches = [0, 1].map(|v| {
+            batch(vec![(
+                "a",
+                Arc::new(Int64Array::from_iter_values(std::iter::repeat_n(
+                    v,
+                    5 * SAMPLE_ROWS,
+                ))) as ArrayRef,
+            )])
+        });
+        let columns = batches.iter().map(|b| b.column(0)).collect::<Vec<_>>();
+        let stats = ColumnStats::sample(&DataType::Int64, &columns)
+            .unwrap()
+            .unwrap();
+        assert_eq!(SAMPLE_ROWS, stats.rows);
+        assert_eq!(2, stats.distinct);
+        assert_eq!(
+            Some(ValueRange {
+                min: 0,
+                max: 1,
+                min_delta: 0,
+                max_delta: 0,
+            }),
+            stats.range
+        );
+    }
+
+    #[test]
+    fn value_ranges() {
+        let range = |values: &[i128]| {
+            let mut range = ValueRange::new(values[0]);
+            let mut prev = None;
+            for v in values {
+                range.observe(*v, prev);
+                prev = Some(*v);
+            }
+            range
+        };
+        assert!(range(&[1, 2, 2, 10]).is_monotonic());
+        assert!(range(&[10, 3, 3, 1]).is_monotonic());
+        assert!(!range(&[1, 3, 2]).is_monotonic());
+        assert_eq!(0, range(&[5, 5]).delta_bits());
+        // deltas from -1 to 2 are stored relative to -1, as 0 to 3:
+        assert_eq!(2, range(&[0, -1, 1, 0, 2]).delta_bits());
+        assert_eq!(
+            65,
+            range(&[i64::MIN as i128, i64::MAX as i128, 0]).delta_bits()
+        );
+    }
+
+    #[test]
+    fn empty_batches_have_no_encodings() {
+        assert!(ColumnEncodings::analyze(&[]).is_empty());
+        let batch = batch(vec![(
+            "a",
+            Arc::new(Int64Array::from_iter_values(std::iter::empty())) as ArrayRef,
+        )]);
+        assert!(ColumnEncodings::analyze(&[batch]).is_empty());
+    }
+
+    #[test]
+    fn apply_to_writer_properties() {
+        let batch = batch(vec![
+            (
+                "tag",
+                Arc::new(StringArray::from_iter_values(
+                    (0..100).map(|i| ["a", "b"][i % 2]),
+                )) as ArrayRef,
+            ),
+            (
+                "count",
+                Arc::new(Int64Array::from_iter_values(
+                    (0..100).map(|i| i / 10 + i * 1000),
+                )) as ArrayRef,
+            ),
+            (
+                "field",
+                Arc::new(Int64Array::from_iter_values((0..100).map(|i| i / 20))) as ArrayRef,
+            ),
+        ]);
+        let encodings = ColumnEncodings::analyze(&[batch.clone()]);
+        let props: WriterProperties = encodings
+            .apply(WriterProperties::builder(), batch.schema().as_ref())
+            .build();
+
+        let tag = ColumnPath::from("tag");
+        assert!(props.dictionary_enabled(&tag));
+
+        let count = ColumnPath::from("count");
+        assert!(!props.dictionary_enabled(&count));
+        assert_eq!(Some(Encoding::DELTA_BINARY_PACKED), props.encoding(&count));
+
+        // low cardinality takes precedence over long runs:
+        let field = ColumnPath::from("field");
+        assert_eq!(Some(ColumnEncoding::Dictionary), encodings.get("field"));
+        assert!(props.dictionary_enabled(&field));
+
+        // run-length encoding only applies to boolean columns:
+        let run_length = ColumnEncodings(BTreeMap::from([(
+            "count".to_string(),
+            ColumnEncoding::RunLength,
+        )]));
+        let props: WriterProperties = run_length
+            .apply(WriterProperties::builder(), batch.schema().as_ref())
+            .build();
+        assert_eq!(None, props.encoding(&count));
+    }
+
+    #[test]
+    fn serialize_encodings() {
+        let batch = batch(vec![
+            (
+                "a",
+                Arc::new(Int64Array::from_iter_values(0..100)) as ArrayRef,
+            ),
+            (
+                "b",
+                Arc::new(Float64Array::from_iter_values((0..100).map(f64::from))) as ArrayRef,
+            ),
+        ]);
+        let encodings = ColumnEncodings::analyze(&[batch]);
+        let json = serde_json::to_string(&encodings).unwrap();
+        assert_eq!(r#"{"a":"delta","b":"plain"}"#, json);
+        let decoded: ColumnEncodings = serde_json::from_str(&json).unwrap();
+        assert_eq!(encodings, decoded);
+    }
+}
@@ -4,9 +4,13 @@
 //! data into parquet files that are persisted to object storage. A snapshot file is written that contains the
 //! metadata of the parquet files that were written in that snapshot.
 
+pub mod backup;
 pub mod chunk;
+pub mod encoding;
 pub mod paths;
 pub mod persister;
+pub mod quota;
+pub mod tag_index;
 pub mod write_buffer;
 
 use anyhow::Context;
@@ -18,10 +22,14 @@ use datafusion::{
     error::DataFusionError,
     execution::context::ExecutionProps,
     logical_expr::interval_arithmetic::Interval,
-    physical_expr::{AnalysisContext, ExprBoundaries, analyze, create_physical_expr},
+    physical_expr::{
+        AnalysisContext, ExprBoundaries, analyze, create_physical_expr,
+        utils::{Guarantee, LiteralGuarantee},
+    },
     prelude::Expr,
     scalar::ScalarValue,
 };
+use encoding::ColumnEncodings;
 use influxdb3_cache::{
     distinct_cache::{CreateDistinctCacheArgs, DistinctCacheProvider},
     last_cache::LastCacheProvider,
@@ -30,15 +38,17 @@ use influxdb3_catalog::catalog::{Catalog, CatalogSequenceNumber, DatabaseSchema,
 use influxdb3_id::{ColumnId, DbId, ParquetFileId, SerdeVecMap, TableId};
 pub use influxdb3_types::write::Precision;
 use influxdb3_wal::{
-    DistinctCacheDefinition, LastCacheDefinition, SnapshotSequenceNumber, Wal,
+    DatabaseQuota, DistinctCacheDefinition, LastCacheDefinition, SnapshotSequenceNumber, Wal,
     WalFileSequenceNumber,
 };
 use iox_query::QueryChunk;
 use iox_time::Time;
 use observability_deps::tracing::debug;
-use schema::TIME_COLUMN_NAME;
+use quota::QuotaTracker;
+use schema::{InfluxColumnType, TIME_COLUMN_NAME};
 use serde::{Deserialize, Serialize};
 use std::{fmt::Debug, sync::Arc, time::Duration};
+use tag_index::TagIndex;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -80,6 +90,11 @@ pub trait DatabaseManager: Debug + Send + Sync + 'static {
         db_name: String,
         table_name: String,
     ) -> Result<(), write_buffer::Error>;
+    async fn set_database_quota(
+        &self,
+        db_name: String,
+        quota: DatabaseQuota,
+    ) -> Result<(), write_buffer::Error>;
 }
 
 /// The buffer is for buffering data in memory and in the wal before it is persisted as parquet files in storage.
@@ -118,6 +133,9 @@ pub trait Bufferer: Debug + Send + Sync + 'static {
 
     /// A channel to watch for when new persisted snapshots are created
     fn watch_persisted_snapshots(&self) -> tokio::sync::watch::Receiver<Option<PersistedSnapshot>>;
+
+    /// Returns the tracker of the resources used by each database
+    fn quota_tracker(&self) -> Arc<QuotaTracker>;
 }
 
 /// ChunkContainer is used by the query engine to get chunks for a given table. Chunks will generally be in the
@@ -335,6 +353,13 @@ pub struct ParquetFile {
     pub min_time: i64,
     /// max time nanos
     pub max_time: i64,
+    /// the encoding chosen for each column when the file was written
+    #[serde(default, skip_serializing_if = "ColumnEncodings::is_empty")]
+    pub column_encodings: ColumnEncodings,
+    /// the series in the file that have each tag value, if the file was indexed; this is shared
+    /// between the copies of the file handed out to queries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag_index: Option<Arc<TagIndex>>,
 }
 
 impl ParquetFile {
@@ -363,6 +388,8 @@ impl ParquetFile {
             chunk_time: 0,
             min_time: 0,
             max_time: 1,
+            column_encodings: Default::default(),
+            tag_index: None,
         }
     }
 }
@@ -403,6 +430,8 @@ pub(crate) fn guess_precision(timestamp: i64) -> Precision {
 pub struct ChunkFilter<'a> {
     time_lower_bound_ns: Option<i64>,
     time_upper_bound_ns: Option<i64>,
+    /// Tag columns, each with the set of values that the filters restrict it to
+    tag_predicates: Vec<(String, Vec<String>)>,
     filters: &'a [Expr],
 }
 
@@ -411,7 +440,9 @@ impl<'a> ChunkFilter<'a> {
     /// a logical query plan.
     ///
     /// This method analyzes the incoming `exprs` to determine if there are any filters on the
-    /// `time` column and attempt to derive the boundaries on `time` from the query.
+    /// `time` column and attempt to derive the boundaries on `time` from the query. It also
+    /// derives the values that any tag columns are restricted to, e.g., by `tag = 'a'` or
+    /// `tag IN ('a', 'b')`.
     pub fn new(table_def: &Arc<TableDefinition>, exprs: &'a [Expr]) -> Result<Self> {
         debug!(input = ?exprs, ">>> creating chunk filter");
         let mut time_interval: Option<Interval> = None;
@@ -489,9 +520,38 @@ impl<'a> ChunkFilter<'a> {
             (None, None)
         };
 
+        // Determine the values that tag columns are restricted to, which are used to test
+        // against the tag index of persisted files:
+        let mut tag_predicates = Vec::new();
+        for expr in exprs {
+            let Ok(physical_expr) = create_physical_expr(expr, &df_schema, &props) else {
+                continue;
+            };
+            for LiteralGuarantee {
+                column,
+                guarantee,
+                literals,
+            } in LiteralGuarantee::analyze(&physical_expr)
+            {
+                if !matches!(guarantee, Guarantee::In)
+                    || !table_def
+                        .column_definition(column.name())
+                        .is_some_and(|def| def.data_type == InfluxColumnType::Tag)
+                {
+                    continue;
+                }
+                let Some(values) = literals.iter().map(tag_value).collect::<Option<Vec<_>>>()
+                else {
+                    continue;
+                };
+                tag_predicates.push((column.name().to_string(), values));
+            }
+        }
+
         Ok(Self {
             time_lower_bound_ns,
             time_upper_bound_ns,
+            tag_predicates,
             filters: exprs,
         })
     }
@@ -507,11 +567,32 @@ impl<'a> ChunkFilter<'a> {
         }
     }
 
+    /// Test the [`TagIndex`] of a persisted file against this filter to check if the file contains
+    /// any series with the tag values the filter is restricted to.
+    pub fn test_tag_index(&self, tag_index: &TagIndex) -> bool {
+        tag_index.has_series_matching(
+            self.tag_predicates
+                .iter()
+                .map(|(tag, values)| (tag.as_str(), values.iter().map(String::as_str))),
+        )
+    }
+
     pub fn original_filters(&self) -> &[Expr] {
         self.filters
     }
 }
 
+/// Get the value of a literal compared against a tag column
+fn tag_value(literal: &ScalarValue) -> Option<String> {
+    match literal {
+        ScalarValue::Utf8(Some(s))
+        | ScalarValue::Utf8View(Some(s))
+        | ScalarValue::LargeUtf8(Some(s)) => Some(s.to_owned()),
+        ScalarValue::Dictionary(_, value) => tag_value(value),
+        _ => None,
+    }
+}
+
 pub mod test_helpers {
     use crate::ChunkFilter;
     use crate::WriteBuffer;
@@ -613,6 +694,8 @@ mod tests {
                 chunk_time: 1123456789,
                 min_time: 11234567777,
                 max_time: 11234567788,
+                column_encodings: Default::default(),
+                tag_index: None,
             },
             ParquetFile {
                 id: ParquetFileId::from(2),
@@ -622,6 +705,8 @@ mod tests {
                 chunk_time: 1123456789,
                 min_time: 11234567777,
                 max_time: 11234567788,
+                column_encodings: Default::default(),
+                tag_index: None,
             },
         ];
         tables_1.insert(table_id_1, parquet_files_1);
@@ -658,6 +743,8 @@ mod tests {
                 chunk_time: 1123456789,
                 min_time: 11234567777,
                 max_time: 11234567788,
+                column_encodings: Default::default(),
+                tag_index: None,
             },
             ParquetFile {
                 id: ParquetFileId::from(5),
@@ -667,6 +754,8 @@ mod tests {
                 chunk_time: 1123456789,
                 min_time: 11234567777,
                 max_time: 11234567788,
+                column_encodings: Default::default(),
+                tag_index: None,
             },
         ];
         tables_2.insert(table_id_2, parquet_files_2);
@@ -712,6 +801,8 @@ mod tests {
                 chunk_time: 1123456789,
                 min_time: 11234567777,
                 max_time: 11234567788,
+                column_encodings: Default::default(),
+                tag_index: None,
             },
             ParquetFile {
                 id: ParquetFileId::from(2),
@@ -721,6 +812,8 @@ mod tests {
                 chunk_time: 1123456789,
                 min_time: 11234567777,
                 max_time: 11234567788,
+                column_encodings: Default::default(),
+                tag_index: None,
             },
         ];
         tables_1.insert(table_id_1, parquet_files_1);
@@ -740,6 +833,8 @@ mod tests {
                 chunk_time: 1123456789,
                 min_time: 11234567777,
                 max_time: 11234567788,
+                column_encodings: Default::default(),
+                tag_index: None,
             },
             ParquetFile {
                 id: ParquetFileId::from(5),
@@ -749,6 +844,8 @@ mod tests {
                 chunk_time: 1123456789,
                 min_time: 11234567777,
                 max_time: 11234567788,
+                column_encodings: Default::default(),
+                tag_index: None,
             },
         ];
         tables_2.insert(table_id_2, parquet_files_2);
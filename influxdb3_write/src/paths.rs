@@ -1,7 +1,7 @@
 use chrono::prelude::*;
 use influxdb3_catalog::catalog::CatalogSequenceNumber;
 use influxdb3_wal::{SnapshotSequenceNumber, WalFileSequenceNumber};
-use object_store::path::Path as ObjPath;
+use object_store::path::{Path as ObjPath, PathPart};
 use std::ops::Deref;
 
 /// File extension for catalog files
@@ -13,6 +13,9 @@ pub const PARQUET_FILE_EXTENSION: &str = "parquet";
 /// File extension for snapshot info files
 pub const SNAPSHOT_INFO_FILE_EXTENSION: &str = "info.json";
 
+/// File extension for backup manifest files
+pub const BACKUP_MANIFEST_FILE_EXTENSION: &str = "manifest.json";
+
 fn object_store_file_stem(n: u64) -> u64 {
     u64::MAX - n
 }
@@ -121,6 +124,70 @@ impl AsRef<ObjPath> for SnapshotInfoFilePath {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupManifestFilePath(ObjPath);
+
+impl BackupManifestFilePath {
+    pub fn new(host_prefix: &str, backup_sequence_number: u64) -> Self {
+        let path = ObjPath::from(format!(
+            "{host_prefix}/backups/manifests/{:020}.{}",
+            object_store_file_stem(backup_sequence_number),
+            BACKUP_MANIFEST_FILE_EXTENSION
+        ));
+        Self(path)
+    }
+
+    pub fn dir(host_prefix: &str) -> Self {
+        Self(ObjPath::from(format!("{host_prefix}/backups/manifests")))
+    }
+}
+
+impl Deref for BackupManifestFilePath {
+    type Target = ObjPath;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl AsRef<ObjPath> for BackupManifestFilePath {
+    fn as_ref(&self) -> &ObjPath {
+        &self.0
+    }
+}
+
+/// The location in the backup area of a file that was backed up from `{host_prefix}/{relative}`
+///
+/// Backed up files are shared between backups, so that each backup only needs to copy the files
+/// that were not already captured by a previous one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupFilePath(ObjPath);
+
+impl BackupFilePath {
+    /// Create the backup location of a file given its path `relative` to the `host_prefix`
+    pub fn new<'a>(host_prefix: &str, relative: impl IntoIterator<Item = PathPart<'a>>) -> Self {
+        Self(Self::dir(host_prefix).parts().chain(relative).collect())
+    }
+
+    pub fn dir(host_prefix: &str) -> Self {
+        Self(ObjPath::from(format!("{host_prefix}/backups/files")))
+    }
+}
+
+impl Deref for BackupFilePath {
+    type Target = ObjPath;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl AsRef<ObjPath> for BackupFilePath {
+    fn as_ref(&self) -> &ObjPath {
+        &self.0
+    }
+}
+
 #[test]
 fn catalog_file_path_new() {
     assert_eq!(
@@ -176,3 +243,26 @@ fn snapshot_info_file_path_new() {
         ObjPath::from("my_host/snapshots/18446744073709551615.info.json")
     );
 }
+
+#[test]
+fn backup_manifest_file_path_new() {
+    assert_eq!(
+        *BackupManifestFilePath::new("my_host", 0),
+        ObjPath::from("my_host/backups/manifests/18446744073709551615.manifest.json")
+    );
+}
+
+#[test]
+fn backup_file_path_new() {
+    assert_eq!(
+        *BackupFilePath::new("my_host", ObjPath::from("wal/00000000001.wal").parts()),
+        ObjPath::from("my_host/backups/files/wal/00000000001.wal")
+    );
+    // parts that were already percent-encoded are not encoded again:
+    assert_eq!(
+        BackupFilePath::new("my_host", ObjPath::from("dbs/a%b/00.parquet").parts())
+            .as_ref()
+            .as_ref(),
+        "my_host/backups/files/dbs/a%25b/00.parquet"
+    );
+}
@@ -2,6 +2,7 @@
 //! storage.
 
 use crate::PersistedSnapshot;
+use crate::encoding::ColumnEncodings;
 use crate::paths::CatalogFilePath;
 use crate::paths::ParquetFilePath;
 use crate::paths::SnapshotInfoFilePath;
@@ -27,7 +28,7 @@ use object_store::path::Path as ObjPath;
 use observability_deps::tracing::info;
 use parquet::arrow::ArrowWriter;
 use parquet::basic::Compression;
-use parquet::file::properties::WriterProperties;
+use parquet::file::properties::{WriterProperties, WriterPropertiesBuilder};
 use parquet::format::FileMetaData;
 use std::any::Any;
 use std::io::Write;
@@ -114,8 +115,9 @@ impl Persister {
     async fn serialize_to_parquet(
         &self,
         batches: SendableRecordBatchStream,
+        column_encodings: &ColumnEncodings,
     ) -> Result<ParquetBytes> {
-        serialize_to_parquet(Arc::clone(&self.mem_pool), batches).await
+        serialize_to_parquet(Arc::clone(&self.mem_pool), batches, column_encodings).await
     }
 
     /// Get the host identifier prefix
@@ -324,15 +326,19 @@ impl Persister {
         Ok(())
     }
 
-    /// Writes a [`SendableRecordBatchStream`] to the Parquet format and persists it to Object Store
-    /// at the given path. Returns the number of bytes written and the file metadata.
+    /// Writes a [`SendableRecordBatchStream`] to the Parquet format, using the given per-column
+    /// encodings, and persists it to Object Store at the given path. Returns the number of bytes
+    /// written and the file metadata.
     pub async fn persist_parquet_file(
         &self,
         path: ParquetFilePath,
         record_batch: SendableRecordBatchStream,
+        column_encodings: &ColumnEncodings,
     ) -> Result<(u64, FileMetaData, ParquetFileDataToCache)> {
         // so we have serialized parquet file bytes
-        let parquet = self.serialize_to_parquet(record_batch).await?;
+        let parquet = self
+            .serialize_to_parquet(record_batch, column_encodings)
+            .await?;
         let bytes_written = parquet.bytes.len() as u64;
         let put_result = self
             .object_store
@@ -363,6 +369,7 @@ impl Persister {
 pub async fn serialize_to_parquet(
     mem_pool: Arc<dyn MemoryPool>,
     batches: SendableRecordBatchStream,
+    column_encodings: &ColumnEncodings,
 ) -> Result<ParquetBytes> {
     // The ArrowWriter::write() call will return an error if any subsequent
     // batch does not match this schema, enforcing schema uniformity.
@@ -374,7 +381,15 @@ pub async fn serialize_to_parquet(
 
     // Construct the arrow serializer with the metadata as part of the parquet
     // file properties.
-    let mut writer = TrackedMemoryArrowWriter::try_new(&mut bytes, Arc::clone(&schema), mem_pool)?;
+    let props = column_encodings
+        .apply(default_writer_properties(), &schema)
+        .build();
+    let mut writer = TrackedMemoryArrowWriter::try_new_with_properties(
+        &mut bytes,
+        Arc::clone(&schema),
+        mem_pool,
+        props,
+    )?;
 
     while let Some(batch) = stream.try_next().await? {
         writer.write(batch)?;
@@ -410,13 +425,26 @@ pub struct TrackedMemoryArrowWriter<W: Write + Send> {
 /// Parquet row group write size
 pub const ROW_GROUP_WRITE_SIZE: usize = 100_000;
 
+/// The [`WriterProperties`] used for all Parquet files, before any per-column settings are applied
+fn default_writer_properties() -> WriterPropertiesBuilder {
+    WriterProperties::builder()
+        .set_compression(Compression::ZSTD(Default::default()))
+        .set_max_row_group_size(ROW_GROUP_WRITE_SIZE)
+}
+
 impl<W: Write + Send> TrackedMemoryArrowWriter<W> {
     /// create a new `TrackedMemoryArrowWriter<`
     pub fn try_new(sink: W, schema: SchemaRef, mem_pool: Arc<dyn MemoryPool>) -> Result<Self> {
-        let props = WriterProperties::builder()
-            .set_compression(Compression::ZSTD(Default::default()))
-            .set_max_row_group_size(ROW_GROUP_WRITE_SIZE)
-            .build();
+        Self::try_new_with_properties(sink, schema, mem_pool, default_writer_properties().build())
+    }
+
+    /// create a new `TrackedMemoryArrowWriter` that writes with the given [`WriterProperties`]
+    pub fn try_new_with_properties(
+        sink: W,
+        schema: SchemaRef,
+        mem_pool: Arc<dyn MemoryPool>,
+        props: WriterProperties,
+    ) -> Result<Self> {
         let inner = ArrowWriter::try_new(sink, schema, Some(props))?;
         let consumer = MemoryConsumer::new("InfluxDB3 ParquetWriter (TrackedMemoryArrowWriter)");
         let reservation = consumer.register(&mem_pool);
@@ -824,6 +852,8 @@ mod tests {
                 chunk_time: 5,
                 min_time: 0,
                 max_time: 1,
+                column_encodings: Default::default(),
+                tag_index: None,
             },
         );
         persister.persist_snapshot(&info_file).await.unwrap();
@@ -933,7 +963,7 @@ mod tests {
         stream_builder.tx().send(Ok(batch2)).await.unwrap();
 
         let parquet = persister
-            .serialize_to_parquet(stream_builder.build())
+            .serialize_to_parquet(stream_builder.build(), &ColumnEncodings::default())
             .await
             .unwrap();
 
@@ -970,7 +1000,11 @@ mod tests {
             WalFileSequenceNumber::new(1),
         );
         let (bytes_written, meta, _) = persister
-            .persist_parquet_file(path.clone(), stream_builder.build())
+            .persist_parquet_file(
+                path.clone(),
+                stream_builder.build(),
+                &ColumnEncodings::default(),
+            )
             .await
             .unwrap();
 
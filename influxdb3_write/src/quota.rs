@@ -0,0 +1,691 @@
+//! Accounting and enforcement of the [`DatabaseQuota`] set on each database.
+//!
+//! The [`QuotaTracker`] keeps the usage of every database: the bytes of parquet that have been
+//! persisted for it, the queries that are running against it along with the memory they have
+//! reserved, and, for databases with a series limit, the distinct series that have been written to
+//! it. Writes are checked with [`QuotaTracker::admit_write`] before they go into the WAL, and
+//! queries hold a [`QueryPermit`] from [`QuotaTracker::start_query`] for as long as they run. The
+//! memory of a query is reserved through a [`QueryMemoryPool`] that wraps the DataFusion memory
+//! pool of the executor, so the limit applies to the memory used while the query runs.
+//!
+//! Series are only counted for databases that have a series limit, and at most up to that limit,
+//! so the memory used for counting is bounded by the limits that are set. Counting starts with the
+//! first write after a limit is set, from the tag indexes of the database's persisted files; files
+//! that were not indexed, and data that is only in the buffer, do not count towards the limit until
+//! their series are written again. Series are then counted once the WAL has been flushed into the
+//! buffer, so writes that are in flight concurrently may take a database over its series limit by
+//! up to one WAL flush worth of new series. The series of a table are forgotten when the table is
+//! deleted, and all usage of a database is forgotten when the database is deleted.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+
+use datafusion::error::DataFusionError;
+use datafusion::execution::memory_pool::{MemoryConsumer, MemoryPool, MemoryReservation};
+use hashbrown::{HashMap, HashSet};
+use influxdb3_catalog::catalog::DatabaseSchema;
+use influxdb3_id::{ColumnId, DbId, TableId};
+use influxdb3_wal::{CatalogOp, DatabaseQuota, FieldData, WalContents, WalOp, WriteBatch};
+use parking_lot::{Mutex, RwLock};
+use thiserror::Error;
+
+use crate::write_buffer::persisted_files::PersistedFiles;
+use crate::{ParquetFile, PersistedSnapshot};
+
+#[derive(Debug, Error)]
+pub enum QuotaError {
+    #[error("database '{db_name}' has reached its limit of {limit} series")]
+    Series { db_name: Arc<str>, limit: u64 },
+
+    #[error("database '{db_name}' has reached its limit of {limit} stored bytes")]
+    StoredBytes { db_name: Arc<str>, limit: u64 },
+
+    #[error("database '{db_name}' has reached its limit of {limit} concurrent queries")]
+    ConcurrentQueries { db_name: Arc<str>, limit: u32 },
+
+    #[error("query against database '{db_name}' exceeded the limit of {limit} bytes of memory")]
+    QueryMemoryBytes { db_name: Arc<str>, limit: u64 },
+}
+
+/// The resources used by each database, see the [module docs][self]
+#[derive(Debug, Default)]
+pub struct QuotaTracker {
+    databases: RwLock<HashMap<DbId, Arc<DatabaseUsage>>>,
+}
+
+#[derive(Debug, Default)]
+struct DatabaseUsage {
+    /// Whether `series` is set, to skip taking its lock for databases without a series limit
+    counts_series: AtomicBool,
+    series: Mutex<Option<SeriesCount>>,
+    stored_bytes: AtomicU64,
+    running_queries: AtomicU32,
+    query_memory_bytes: AtomicU64,
+    rejected_writes: AtomicU64,
+    rejected_queries: AtomicU64,
+}
+
+/// The distinct series of each table in a database, counted up to the database's series limit
+#[derive(Debug)]
+struct SeriesCount {
+    limit: u64,
+    count: u64,
+    tables: HashMap<TableId, HashSet<u64>>,
+}
+
+/// A point in time view of the resources used by a database
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UsageSnapshot {
+    /// The series counted for the database, which is zero if it has no series limit
+    pub series: u64,
+    pub stored_bytes: u64,
+    pub running_queries: u32,
+    pub query_memory_bytes: u64,
+    pub rejected_writes: u64,
+    pub rejected_queries: u64,
+}
+
+impl QuotaTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a tracker seeded with the sizes of the files in the given persisted snapshots
+    pub fn new_from_persisted_snapshots(persisted_snapshots: &[PersistedSnapshot]) -> Self {
+        let tracker = Self::new();
+        for snapshot in persisted_snapshots {
+            for (db_id, tables) in &snapshot.databases {
+                for file in tables.tables.values().flatten() {
+                    tracker.observe_persisted_file(*db_id, file);
+                }
+            }
+        }
+        tracker
+    }
+
+    fn usage(&self, db_id: DbId) -> Arc<DatabaseUsage> {
+        if let Some(usage) = self.databases.read().get(&db_id) {
+            return Arc::clone(usage);
+        }
+        Arc::clone(self.databases.write().entry(db_id).or_default())
+    }
+
+    /// Count the series in the writes of the given WAL contents, and forget the usage of deleted
+    /// tables and databases
+    pub fn observe_wal_contents(&self, contents: &WalContents) {
+        for op in &contents.ops {
+            match op {
+                WalOp::Write(batch) => self.observe_write(batch),
+                WalOp::Catalog(batch) => {
+                    let batch = batch.batch();
+                    for op in &batch.ops {
+                        self.observe_catalog_op(batch.database_id, op);
+                    }
+                }
+                WalOp::Noop(_) => (),
+            }
+        }
+    }
+
+    /// Count the series in the given write, if the database has a series limit
+    pub fn observe_write(&self, batch: &WriteBatch) {
+        let Some(usage) = self.databases.read().get(&batch.database_id).cloned() else {
+            return;
+        };
+        if !usage.counts_series.load(Ordering::Acquire) {
+            return;
+        }
+        if let Some(series) = usage.series.lock().as_mut() {
+            for (table_id, hash) in write_series(batch) {
+                series.insert(table_id, hash);
+            }
+        }
+    }
+
+    fn observe_catalog_op(&self, db_id: DbId, op: &CatalogOp) {
+        match op {
+            CatalogOp::DeleteDatabase(_) => {
+                self.databases.write().remove(&db_id);
+            }
+            CatalogOp::DeleteTable(table) => {
+                let Some(usage) = self.databases.read().get(&db_id).cloned() else {
+                    return;
+                };
+                if let Some(series) = usage.series.lock().as_mut() {
+                    series.remove_table(&table.table_id);
+                }
+            }
+            CatalogOp::SetDatabaseQuota(DatabaseQuota {
+                max_series: None, ..
+            }) => {
+                let Some(usage) = self.databases.read().get(&db_id).cloned() else {
+                    return;
+                };
+                usage.counts_series.store(false, Ordering::Release);
+                usage.series.lock().take();
+            }
+            _ => (),
+        }
+    }
+
+    /// Count the bytes of a file that has been persisted
+    pub fn observe_persisted_file(&self, db_id: DbId, file: &ParquetFile) {
+        self.usage(db_id)
+            .stored_bytes
+            .fetch_add(file.size_bytes, Ordering::Relaxed);
+    }
+
+    /// Check that the given write, which has been validated but not yet written to the WAL, does
+    /// not take the database over its quota. If the database has a series limit, but its series
+    /// are not counted yet, counting starts from the `persisted_files` of the database.
+    pub fn admit_write(
+        &self,
+        db_schema: &DatabaseSchema,
+        persisted_files: &PersistedFiles,
+        batch: &WriteBatch,
+    ) -> Result<(), QuotaError> {
+        let quota = db_schema.quota;
+        if quota.max_series.is_none() && quota.max_stored_bytes.is_none() {
+            return Ok(());
+        }
+        let usage = self.usage(db_schema.id);
+        let result = check_write(db_schema, persisted_files, &quota, &usage, batch);
+        if result.is_err() {
+            usage.rejected_writes.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Admit a query against the given database, the returned permit must be held for as long as
+    /// the query runs
+    pub fn start_query(&self, db_schema: &DatabaseSchema) -> Result<QueryPermit, QuotaError> {
+        let usage = self.usage(db_schema.id);
+        let limit = db_schema.quota.max_concurrent_queries;
+        let admitted = usage
+            .running_queries
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |running| {
+                limit
+                    .is_none_or(|limit| running < limit)
+                    .then_some(running + 1)
+            })
+            .is_ok();
+        if !admitted {
+            usage.rejected_queries.fetch_add(1, Ordering::Relaxed);
+            return Err(QuotaError::ConcurrentQueries {
+                db_name: Arc::clone(&db_schema.name),
+                limit: limit.unwrap_or_default(),
+            });
+        }
+        Ok(QueryPermit {
+            db_name: Arc::clone(&db_schema.name),
+            max_memory_bytes: db_schema.quota.max_query_memory_bytes,
+            usage,
+            memory_bytes: AtomicU64::new(0),
+            exceeded_memory: AtomicBool::new(false),
+        })
+    }
+
+    /// The usage of the given database
+    pub fn usage_snapshot(&self, db_id: DbId) -> UsageSnapshot {
+        let Some(usage) = self.databases.read().get(&db_id).cloned() else {
+            return UsageSnapshot::default();
+        };
+        UsageSnapshot {
+            series: usage.series.lock().as_ref().map_or(0, |s| s.count),
+            stored_bytes: usage.stored_bytes.load(Ordering::Relaxed),
+            running_queries: usage.running_queries.load(Ordering::Relaxed),
+            query_memory_bytes: usage.query_memory_bytes.load(Ordering::Relaxed),
+            rejected_writes: usage.rejected_writes.load(Ordering::Relaxed),
+            rejected_queries: usage.rejected_queries.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl SeriesCount {
+    /// Count the series in the tag indexes of the database's persisted files, up to `limit`
+    fn from_persisted_files(
+        db_schema: &DatabaseSchema,
+        persisted_files: &PersistedFiles,
+        limit: u64,
+    ) -> Self {
+        let mut series = Self {
+            limit,
+            count: 0,
+            tables: HashMap::new(),
+        };
+        for table_def in db_schema.tables() {
+            for file in persisted_files.get_files(db_schema.id, table_def.table_id) {
+                let Some(tag_index) = file.tag_index.as_ref() else {
+                    continue;
+                };
+                for tags in tag_index.series() {
+                    if series.count >= limit {
+                        return series;
+                    }
+                    let tags = tags.into_iter().filter_map(|(name, value)| {
+                        table_def.column_name_to_id(name).map(|id| (id, value))
+                    });
+                    series.insert(table_def.table_id, series_hash(table_def.table_id, tags));
+                }
+            }
+        }
+        series
+    }
+
+    fn contains(&self, table_id: &TableId, hash: u64) -> bool {
+        self.tables
+            .get(table_id)
+            .is_some_and(|series| series.contains(&hash))
+    }
+
+    /// Count a series, unless the limit has already been reached
+    fn insert(&mut self, table_id: TableId, hash: u64) {
+        if self.count < self.limit && self.tables.entry(table_id).or_default().insert(hash) {
+            self.count += 1;
+        }
+    }
+
+    fn remove_table(&mut self, table_id: &TableId) {
+        if let Some(series) = self.tables.remove(table_id) {
+            self.count -= series.len() as u64;
+        }
+    }
+}
+
+fn check_write(
+    db_schema: &DatabaseSchema,
+    persisted_files: &PersistedFiles,
+    quota: &DatabaseQuota,
+    usage: &DatabaseUsage,
+    batch: &WriteBatch,
+) -> Result<(), QuotaError> {
+    if let Some(limit) = quota.max_stored_bytes {
+        if usage.stored_bytes.load(Ordering::Relaxed) >= limit {
+            return Err(QuotaError::StoredBytes {
+                db_name: Arc::clone(&db_schema.name),
+                limit,
+            });
+        }
+    }
+    if let Some(limit) = quota.max_series {
+        let mut series = usage.series.lock();
+        let series = series.get_or_insert_with(|| {
+            usage.counts_series.store(true, Ordering::Release);
+            SeriesCount::from_persisted_files(db_schema, persisted_files, limit)
+        });
+        series.limit = limit;
+        let new_series = write_series(batch)
+            .filter(|(table_id, hash)| !series.contains(table_id, *hash))
+            .collect::<HashSet<_>>();
+        if !new_series.is_empty() && series.count + new_series.len() as u64 > limit {
+            return Err(QuotaError::Series {
+                db_name: Arc::clone(&db_schema.name),
+                limit,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The table and series hash of each row in the given write
+fn write_series(batch: &WriteBatch) -> impl Iterator<Item = (TableId, u64)> + '_ {
+    batch
+        .table_chunks
+        .iter()
+        .flat_map(|(table_id, chunks)| {
+            chunks
+                .chunk_time_to_chunk
+                .values()
+                .flat_map(|chunk| &chunk.rows)
+                .map(move |row| (*table_id, row))
+        })
+        .map(|(table_id, row)| {
+            let tags = row.fields.iter().filter_map(|f| match &f.value {
+                FieldData::Tag(v) | FieldData::Key(v) => Some((f.id, v.as_str())),
+                _ => None,
+            });
+            (table_id, series_hash(table_id, tags))
+        })
+}
+
+fn series_hash<'a>(table_id: TableId, tags: impl Iterator<Item = (ColumnId, &'a str)>) -> u64 {
+    let mut tags = tags.collect::<Vec<_>>();
+    tags.sort_unstable();
+    let mut hasher = DefaultHasher::new();
+    table_id.hash(&mut hasher);
+    tags.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Admission of a running query, which releases its slot and its memory when dropped
+#[derive(Debug)]
+pub struct QueryPermit {
+    db_name: Arc<str>,
+    max_memory_bytes: Option<u64>,
+    usage: Arc<DatabaseUsage>,
+    memory_bytes: AtomicU64,
+    /// Whether the query has been counted as rejected for exceeding its memory limit, so that it
+    /// is only counted once however often it runs into the limit
+    exceeded_memory: AtomicBool,
+}
+
+impl QueryPermit {
+    /// Reserve memory for the query, unless it takes the query over the limit on the memory a
+    /// query may use
+    pub fn try_reserve_memory(&self, bytes: u64) -> Result<(), QuotaError> {
+        let limit = self.max_memory_bytes;
+        let reserved = self
+            .memory_bytes
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |reserved| {
+                let reserved = reserved.saturating_add(bytes);
+                limit
+                    .is_none_or(|limit| reserved <= limit)
+                    .then_some(reserved)
+            })
+            .is_ok();
+        if !reserved {
+            if !self.exceeded_memory.swap(true, Ordering::Relaxed) {
+                self.usage.rejected_queries.fetch_add(1, Ordering::Relaxed);
+            }
+            return Err(QuotaError::QueryMemoryBytes {
+                db_name: Arc::clone(&self.db_name),
+                limit: limit.unwrap_or_default(),
+            });
+        }
+        self.usage
+            .query_memory_bytes
+            .fetch_add(bytes, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Reserve memory for the query regardless of its limit
+    pub fn reserve_memory(&self, bytes: u64) {
+        self.memory_bytes.fetch_add(bytes, Ordering::AcqRel);
+        self.usage
+            .query_memory_bytes
+            .fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Release memory that was reserved for the query
+    pub fn release_memory(&self, bytes: u64) {
+        self.memory_bytes.fetch_sub(bytes, Ordering::AcqRel);
+        self.usage
+            .query_memory_bytes
+            .fetch_sub(bytes, Ordering::Relaxed);
+    }
+}
+
+impl Drop for QueryPermit {
+    fn drop(&mut self) {
+        self.usage
+            .query_memory_bytes
+            .fetch_sub(*self.memory_bytes.get_mut(), Ordering::Relaxed);
+        self.usage.running_queries.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// The DataFusion [`MemoryPool`] of a single query, which reserves the memory of the query against
+/// its [`QueryPermit`] before reserving it from the memory pool of the executor
+#[derive(Debug)]
+pub struct QueryMemoryPool {
+    inner: Arc<dyn MemoryPool>,
+    permit: Arc<QueryPermit>,
+}
+
+impl QueryMemoryPool {
+    pub fn new(inner: Arc<dyn MemoryPool>, permit: Arc<QueryPermit>) -> Self {
+        Self { inner, permit }
+    }
+}
+
+impl MemoryPool for QueryMemoryPool {
+    fn register(&self, consumer: &MemoryConsumer) {
+        self.inner.register(consumer)
+    }
+
+    fn unregister(&self, consumer: &MemoryConsumer) {
+        self.inner.unregister(consumer)
+    }
+
+    fn grow(&self, reservation: &MemoryReservation, additional: usize) {
+        self.permit.reserve_memory(additional as u64);
+        self.inner.grow(reservation, additional)
+    }
+
+    fn shrink(&self, reservation: &MemoryReservation, shrink: usize) {
+        self.permit.release_memory(shrink as u64);
+        self.inner.shrink(reservation, shrink)
+    }
+
+    fn try_grow(
+        &self,
+        reservation: &MemoryReservation,
+        additional: usize,
+    ) -> Result<(), DataFusionError> {
+        self.permit
+            .try_reserve_memory(additional as u64)
+            .map_err(|e| DataFusionError::ResourcesExhausted(e.to_string()))?;
+        self.inner
+            .try_grow(reservation, additional)
+            .inspect_err(|_| self.permit.release_memory(additional as u64))
+    }
+
+    fn reserved(&self) -> usize {
+        self.permit.memory_bytes.load(Ordering::Acquire) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{ArrayRef, RecordBatch, StringArray};
+    use data_types::NamespaceName;
+    use datafusion::execution::memory_pool::GreedyMemoryPool;
+    use influxdb3_catalog::catalog::Catalog;
+    use influxdb3_wal::{DeleteTableDefinition, Gen1Duration};
+    use iox_time::Time;
+
+    use super::*;
+    use crate::Precision;
+    use crate::tag_index::TagIndex;
+    use crate::write_buffer::validator::WriteValidator;
+
+    fn write(catalog: &Arc<Catalog>, lp: &str) -> WriteBatch {
+        WriteValidator::initialize(NamespaceName::new("foo").unwrap(), Arc::clone(catalog), 0)
+            .unwrap()
+            .v1_parse_lines_and_update_schema(
+                lp,
+                false,
+                Time::from_timestamp_nanos(0),
+                Precision::Nanosecond,
+            )
+            .unwrap()
+            .convert_lines_to_buffer(Gen1Duration::new_1m())
+            .valid_data
+    }
+
+    fn db_schema(catalog: &Catalog, quota: DatabaseQuota) -> DatabaseSchema {
+        let mut db_schema = catalog.db_schema("foo").unwrap().as_ref().clone();
+        db_schema.quota = quota;
+        db_schema
+    }
+
+    #[test]
+    fn series_limit() {
+        let catalog = Arc::new(Catalog::new("node".into(), "instance".into()));
+        let tracker = QuotaTracker::new();
+        let files = PersistedFiles::new();
+        let first = write(&catalog, "cpu,host=a usage=1 1\ncpu,host=b usage=1 2");
+        let db_schema = db_schema(
+            &catalog,
+            DatabaseQuota {
+                max_series: Some(2),
+                ..Default::default()
+            },
+        );
+        tracker.admit_write(&db_schema, &files, &first).unwrap();
+        tracker.observe_write(&first);
+        assert_eq!(2, tracker.usage_snapshot(db_schema.id).series);
+
+        // existing series can still be written to, but new ones are rejected:
+        let existing = write(&catalog, "cpu,host=b usage=2 3");
+        tracker.admit_write(&db_schema, &files, &existing).unwrap();
+        let new = write(&catalog, "cpu,host=b usage=2 3\ncpu,host=c usage=1 4");
+        assert!(matches!(
+            tracker.admit_write(&db_schema, &files, &new),
+            Err(QuotaError::Series { limit: 2, .. })
+        ));
+        // the same tags in another table are another series:
+        let other_table = write(&catalog, "mem,host=a usage=1 1");
+        assert!(
+            tracker
+                .admit_write(&db_schema, &files, &other_table)
+                .is_err()
+        );
+        // series that were admitted concurrently are only counted up to the limit:
+        tracker.observe_write(&new);
+        assert_eq!(2, tracker.usage_snapshot(db_schema.id).series);
+
+        let usage = tracker.usage_snapshot(db_schema.id);
+        assert_eq!(2, usage.series);
+        assert_eq!(2, usage.rejected_writes);
+    }
+
+    #[test]
+    fn series_are_only_counted_with_a_limit() {
+        let catalog = Arc::new(Catalog::new("node".into(), "instance".into()));
+        let tracker = QuotaTracker::new();
+        let files = PersistedFiles::new();
+        let batch = write(&catalog, "cpu,host=a usage=1 1\nmem,host=a usage=1 1");
+        let unlimited = db_schema(&catalog, DatabaseQuota::default());
+        tracker.admit_write(&unlimited, &files, &batch).unwrap();
+        tracker.observe_write(&batch);
+        assert_eq!(0, tracker.usage_snapshot(unlimited.id).series);
+
+        let limited = db_schema(
+            &catalog,
+            DatabaseQuota {
+                max_series: Some(10),
+                ..Default::default()
+            },
+        );
+        tracker.admit_write(&limited, &files, &batch).unwrap();
+        tracker.observe_write(&batch);
+        assert_eq!(2, tracker.usage_snapshot(limited.id).series);
+
+        // the series of a deleted table are forgotten:
+        let table = limited.table_definition("mem").unwrap();
+        tracker.observe_catalog_op(
+            limited.id,
+            &CatalogOp::DeleteTable(DeleteTableDefinition {
+                database_id: limited.id,
+                database_name: Arc::clone(&limited.name),
+                table_id: table.table_id,
+                table_name: Arc::clone(&table.table_name),
+                deletion_time: 0,
+            }),
+        );
+        assert_eq!(1, tracker.usage_snapshot(limited.id).series);
+
+        // as are all series once the limit is removed:
+        tracker.observe_catalog_op(limited.id, &CatalogOp::SetDatabaseQuota(Default::default()));
+        tracker.observe_write(&batch);
+        assert_eq!(0, tracker.usage_snapshot(limited.id).series);
+    }
+
+    #[test]
+    fn series_are_counted_from_persisted_files() {
+        let catalog = Arc::new(Catalog::new("node".into(), "instance".into()));
+        let tracker = QuotaTracker::new();
+        let batch = write(&catalog, "cpu,host=a usage=1 1");
+        let db_schema = db_schema(
+            &catalog,
+            DatabaseQuota {
+                max_series: Some(3),
+                ..Default::default()
+            },
+        );
+        let table_id = db_schema.table_name_to_id("cpu").unwrap();
+        let hosts = StringArray::from(vec!["a", "b"]);
+        let tag_index = TagIndex::build(
+            &[RecordBatch::try_from_iter([("host", Arc::new(hosts) as ArrayRef)]).unwrap()],
+            &["host"],
+        );
+        let files = PersistedFiles::new();
+        files.add_persisted_file(
+            &db_schema.id,
+            &table_id,
+            &ParquetFile {
+                tag_index: tag_index.map(Arc::new),
+                ..ParquetFile::create_for_test("file.parquet")
+            },
+        );
+
+        // the series of the persisted file are counted before the write is checked:
+        tracker.admit_write(&db_schema, &files, &batch).unwrap();
+        assert_eq!(2, tracker.usage_snapshot(db_schema.id).series);
+        let new = write(&catalog, "cpu,host=c usage=1 1\ncpu,host=d usage=1 1");
+        assert!(tracker.admit_write(&db_schema, &files, &new).is_err());
+    }
+
+    #[test]
+    fn concurrent_query_and_memory_limits() {
+        let catalog = Arc::new(Catalog::new("node".into(), "instance".into()));
+        let tracker = QuotaTracker::new();
+        write(&catalog, "cpu,host=a usage=1 1");
+        let db_schema = db_schema(
+            &catalog,
+            DatabaseQuota {
+                max_concurrent_queries: Some(2),
+                max_query_memory_bytes: Some(100),
+                ..Default::default()
+            },
+        );
+
+        let first = Arc::new(tracker.start_query(&db_schema).unwrap());
+        let second = Arc::new(tracker.start_query(&db_schema).unwrap());
+        assert!(matches!(
+            tracker.start_query(&db_schema),
+            Err(QuotaError::ConcurrentQueries { limit: 2, .. })
+        ));
+
+        // the limit applies to each query on its own:
+        let executor_pool: Arc<dyn MemoryPool> = Arc::new(GreedyMemoryPool::new(1000));
+        let first_pool: Arc<dyn MemoryPool> = Arc::new(QueryMemoryPool::new(
+            Arc::clone(&executor_pool),
+            Arc::clone(&first),
+        ));
+        let second_pool: Arc<dyn MemoryPool> = Arc::new(QueryMemoryPool::new(
+            Arc::clone(&executor_pool),
+            Arc::clone(&second),
+        ));
+        let mut first_reservation = MemoryConsumer::new("first").register(&first_pool);
+        let mut second_reservation = MemoryConsumer::new("second").register(&second_pool);
+        first_reservation.try_grow(60).unwrap();
+        second_reservation.try_grow(60).unwrap();
+        let error = first_reservation.try_grow(50).unwrap_err();
+        assert!(
+            matches!(error, DataFusionError::ResourcesExhausted(_)),
+            "{error}"
+        );
+        first_reservation.try_grow(40).unwrap();
+        assert_eq!(160, tracker.usage_snapshot(db_schema.id).query_memory_bytes);
+        assert_eq!(160, executor_pool.reserved());
+
+        // memory that is freed can be reserved again:
+        first_reservation.shrink(50);
+        first_reservation.try_grow(50).unwrap();
+        assert_eq!(100, first_pool.reserved());
+
+        drop(first_reservation);
+        drop(first_pool);
+        drop(first);
+        let usage = tracker.usage_snapshot(db_schema.id);
+        assert_eq!(1, usage.running_queries);
+        assert_eq!(60, usage.query_memory_bytes);
+        assert_eq!(2, usage.rejected_queries);
+        let _third = tracker.start_query(&db_schema).unwrap();
+        drop(second_reservation);
+        assert_eq!(0, executor_pool.reserved());
+    }
+}
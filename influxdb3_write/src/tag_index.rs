@@ -0,0 +1,346 @@
+//! Inverted index from tag values to the series in a persisted file.
+//!
+//! When the buffer is snapshotted, the sorted and deduplicated data for each table is scanned to
+//! number its distinct series, and a bitmap of those series is built for every tag value. The
+//! index is recorded on the [`ParquetFile`][crate::ParquetFile] in the snapshot, and is consulted
+//! by the [`ChunkFilter`][crate::ChunkFilter] when planning a query, so that files containing no
+//! series matching the query's tag predicates are not read at all.
+//!
+//! Since the index is stored inline in the snapshot and kept in memory for every persisted file,
+//! its size is capped by [`MAX_INDEX_BYTES`]; files whose index would be larger are not indexed, and
+//! are always read by queries, as are files persisted before indexing was introduced.
+
+use std::collections::BTreeMap;
+
+use arrow::array::{Array, AsArray, RecordBatch};
+use arrow::compute::cast;
+use arrow::datatypes::DataType;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use croaring::{Bitmap, Portable};
+use observability_deps::tracing::{debug, warn};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Files with more series than this are not indexed, to bound the work of building the index
+pub const MAX_INDEXED_SERIES: u32 = 100_000;
+
+/// Files whose index would take more than this many bytes in the snapshot are not indexed, to
+/// bound the size of the snapshot files and of the persisted files held in memory
+pub const MAX_INDEX_BYTES: usize = 64 * 1024;
+
+/// The series in a file that have each value of each tag
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagIndex {
+    series_count: u32,
+    tags: BTreeMap<String, BTreeMap<String, SeriesSet>>,
+}
+
+impl TagIndex {
+    /// Build an index of the `tag_columns` in the given batches, which must be sorted on them.
+    ///
+    /// Returns `None` if there are no tag columns to index, the batches contain more than
+    /// [`MAX_INDEXED_SERIES`] series, or the index would be larger than [`MAX_INDEX_BYTES`].
+    pub fn build(batches: &[RecordBatch], tag_columns: &[&str]) -> Option<Self> {
+        let schema = batches.first()?.schema();
+        let indices = tag_columns
+            .iter()
+            .filter_map(|name| schema.index_of(name).ok().map(|i| (*name, i)))
+            .collect::<Vec<_>>();
+        if indices.is_empty() {
+            return None;
+        }
+
+        let mut index = Self::default();
+        let mut previous: Option<Vec<Option<String>>> = None;
+        for batch in batches {
+            let columns = indices
+                .iter()
+                .map(|(name, i)| match cast(batch.column(*i), &DataType::Utf8) {
+                    Ok(array) => Some(array),
+                    Err(error) => {
+                        warn!(%error, column = name, "unable to index tag column");
+                        None
+                    }
+                })
+                .collect::<Option<Vec<_>>>()?;
+            let columns = columns
+                .iter()
+                .map(|a| a.as_string::<i32>())
+                .collect::<Vec<_>>();
+
+            for row in 0..batch.num_rows() {
+                let values = columns
+                    .iter()
+                    .map(|c| c.is_valid(row).then(|| c.value(row)));
+                // rows are sorted on the tags, so a series starts wherever any tag value changes:
+                if previous
+                    .as_ref()
+                    .is_some_and(|p| p.iter().map(|v| v.as_deref()).eq(values.clone()))
+                {
+                    continue;
+                }
+                let series_id = index.series_count;
+                index.series_count += 1;
+                if index.series_count > MAX_INDEXED_SERIES {
+                    return None;
+                }
+                for ((name, _), value) in indices.iter().zip(values.clone()) {
+                    let Some(value) = value else { continue };
+                    index
+                        .tags
+                        .entry(name.to_string())
+                        .or_default()
+                        .entry(value.to_string())
+                        .or_default()
+                        .0
+                        .add(series_id);
+                }
+                previous.replace(values.map(|v| v.map(str::to_string)).collect());
+            }
+        }
+
+        for SeriesSet(series) in index
+            .tags
+            .values_mut()
+            .flat_map(|values| values.values_mut())
+        {
+            series.run_optimize();
+        }
+        let encoded_size = index.encoded_size();
+        if encoded_size > MAX_INDEX_BYTES {
+            debug!(
+                encoded_size,
+                series_count = index.series_count,
+                "tag index is too large, not indexing file"
+            );
+            return None;
+        }
+        Some(index)
+    }
+
+    /// The approximate number of bytes the index takes when serialized in a snapshot
+    pub fn encoded_size(&self) -> usize {
+        self.tags
+            .iter()
+            .map(|(tag, values)| {
+                tag.len()
+                    + values
+                        .iter()
+                        .map(|(value, SeriesSet(series))| {
+                            // quotes and separators, plus the base64-encoded bitmap:
+                            value.len()
+                                + 6
+                                + series
+                                    .get_serialized_size_in_bytes::<Portable>()
+                                    .div_ceil(3)
+                                    * 4
+                        })
+                        .sum::<usize>()
+            })
+            .sum()
+    }
+
+    /// The number of distinct series in the file
+    pub fn series_count(&self) -> u32 {
+        self.series_count
+    }
+
+    /// The tag values of each series in the file, indexed by series identifier; tags that a
+    /// series does not have a value for are omitted
+    pub fn series(&self) -> Vec<Vec<(&str, &str)>> {
+        let mut series = vec![vec![]; self.series_count as usize];
+        for (tag, values) in &self.tags {
+            for (value, SeriesSet(ids)) in values {
+                for id in ids.iter() {
+                    if let Some(s) = series.get_mut(id as usize) {
+                        s.push((tag.as_str(), value.as_str()));
+                    }
+                }
+            }
+        }
+        series
+    }
+
+    /// The series that have any of the given `values` for the `tag`
+    pub fn series_matching<'a>(
+        &self,
+        tag: &str,
+        values: impl IntoIterator<Item = &'a str>,
+    ) -> Bitmap {
+        let mut series = Bitmap::new();
+        if let Some(index) = self.tags.get(tag) {
+            for value in values {
+                if let Some(SeriesSet(s)) = index.get(value) {
+                    series.or_inplace(s);
+                }
+            }
+        }
+        series
+    }
+
+    /// Check if any series matches all of the given predicates, each of which selects the series
+    /// that have any of a set of values for a tag
+    pub fn has_series_matching<'a, V>(
+        &self,
+        predicates: impl IntoIterator<Item = (&'a str, V)>,
+    ) -> bool
+    where
+        V: IntoIterator<Item = &'a str>,
+    {
+        let mut selected: Option<Bitmap> = None;
+        for (tag, values) in predicates {
+            let mut series = self.series_matching(tag, values);
+            if let Some(selected) = &selected {
+                series.and_inplace(selected);
+            }
+            if series.is_empty() {
+                return false;
+            }
+            selected.replace(series);
+        }
+        true
+    }
+}
+
+/// A set of series identifiers, serialized as a base64-encoded portable roaring bitmap
+#[derive(Debug, Clone, Default, PartialEq)]
+struct SeriesSet(Bitmap);
+
+impl Eq for SeriesSet {}
+
+impl Serialize for SeriesSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&BASE64.encode(self.0.serialize::<Portable>()))
+    }
+}
+
+impl<'de> Deserialize<'de> for SeriesSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        let bytes = BASE64.decode(encoded).map_err(serde::de::Error::custom)?;
+        Bitmap::try_deserialize::<Portable>(&bytes)
+            .map(Self)
+            .ok_or_else(|| serde::de::Error::custom("invalid series bitmap"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{ArrayRef, DictionaryArray, Int64Array, StringArray};
+    use arrow::datatypes::Int32Type;
+
+    use super::*;
+
+    fn batch(region: &[&str], host: &[Option<&str>]) -> RecordBatch {
+        let region: DictionaryArray<Int32Type> = region.iter().copied().collect();
+        let host: DictionaryArray<Int32Type> = host.iter().copied().collect();
+        let usage = Int64Array::from_iter_values(0..region.len() as i64);
+        RecordBatch::try_from_iter([
+            ("region", Arc::new(region) as ArrayRef),
+            ("host", Arc::new(host) as ArrayRef),
+            ("usage", Arc::new(usage) as ArrayRef),
+        ])
+        .unwrap()
+    }
+
+    fn ids(bitmap: Bitmap) -> Vec<u32> {
+        bitmap.iter().collect()
+    }
+
+    #[test]
+    fn build_numbers_series_across_batches() {
+        let batches = [
+            batch(
+                &["east", "east", "east", "west"],
+                &[Some("a"), Some("a"), Some("b"), Some("a")],
+            ),
+            // the first row continues the last series of the previous batch:
+            batch(&["west", "west"], &[Some("a"), None]),
+        ];
+        let index = TagIndex::build(&batches, &["region", "host"]).unwrap();
+
+        assert_eq!(4, index.series_count());
+        assert_eq!(vec![0, 1], ids(index.series_matching("region", ["east"])));
+        assert_eq!(vec![2, 3], ids(index.series_matching("region", ["west"])));
+        assert_eq!(vec![0, 2], ids(index.series_matching("host", ["a"])));
+        assert_eq!(
+            vec![0, 1, 2],
+            ids(index.series_matching("host", ["a", "b", "c"]))
+        );
+        assert!(index.series_matching("host", ["c"]).is_empty());
+        assert!(index.series_matching("az", ["a"]).is_empty());
+        assert_eq!(
+            vec![
+                vec![("host", "a"), ("region", "east")],
+                vec![("host", "b"), ("region", "east")],
+                vec![("host", "a"), ("region", "west")],
+                vec![("region", "west")],
+            ],
+            index.series()
+        );
+    }
+
+    #[test]
+    fn has_series_matching() {
+        let batches = [batch(
+            &["east", "east", "west"],
+            &[Some("a"), Some("b"), Some("c")],
+        )];
+        let index = TagIndex::build(&batches, &["region", "host"]).unwrap();
+
+        let no_predicates: [(&str, Vec<&str>); 0] = [];
+        assert!(index.has_series_matching(no_predicates));
+        assert!(index.has_series_matching([("region", vec!["east"])]));
+        assert!(index.has_series_matching([("region", vec!["east"]), ("host", vec!["b", "c"])]));
+        assert!(!index.has_series_matching([("region", vec!["west"]), ("host", vec!["a", "b"])]));
+        assert!(!index.has_series_matching([("region", vec!["north"])]));
+        assert!(!index.has_series_matching([("az", vec!["a"])]));
+    }
+
+    #[test]
+    fn build_without_tags() {
+        let batches = [batch(&["east"], &[Some("a")])];
+        assert_eq!(None, TagIndex::build(&batches, &[]));
+        assert_eq!(None, TagIndex::build(&batches, &["az"]));
+        assert_eq!(None, TagIndex::build(&[], &["region"]));
+    }
+
+    fn hosts(count: u32, len: usize) -> [RecordBatch; 1] {
+        let hosts = (0..count).map(|i| format!("{i:0len$}")).collect::<Vec<_>>();
+        let host = StringArray::from_iter_values(&hosts);
+        [RecordBatch::try_from_iter([("host", Arc::new(host) as ArrayRef)]).unwrap()]
+    }
+
+    #[test]
+    fn build_limits_series() {
+        assert_eq!(
+            None,
+            TagIndex::build(&hosts(MAX_INDEXED_SERIES + 1, 1), &["host"])
+        );
+        assert_eq!(
+            Some(1_000),
+            TagIndex::build(&hosts(1_000, 4), &["host"]).map(|i| i.series_count())
+        );
+    }
+
+    #[test]
+    fn build_limits_size() {
+        let index = TagIndex::build(&hosts(1_000, 4), &["host"]).unwrap();
+        let json = serde_json::to_string(&index).unwrap();
+        assert!(index.encoded_size() <= json.len());
+        assert!(index.encoded_size() >= json.len() / 2);
+
+        // the same number of series with long tag values makes the index too large:
+        assert_eq!(None, TagIndex::build(&hosts(1_000, 100), &["host"]));
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let batches = [batch(&["east", "west"], &[Some("a"), Some("b")])];
+        let index = TagIndex::build(&batches, &["region", "host"]).unwrap();
+        let json = serde_json::to_string(&index).unwrap();
+        assert_eq!(index, serde_json::from_str(&json).unwrap());
+    }
+}
@@ -8,6 +8,7 @@ use tokio::sync::{oneshot, watch::Receiver};
 pub mod validator;
 
 use crate::persister::Persister;
+use crate::quota::{QuotaError, QuotaTracker};
 use crate::write_buffer::persisted_files::PersistedFiles;
 use crate::write_buffer::queryable_buffer::QueryableBuffer;
 use crate::write_buffer::validator::WriteValidator;
@@ -35,8 +36,8 @@ use influxdb3_id::{ColumnId, DbId, TableId};
 use influxdb3_wal::FieldDataType;
 use influxdb3_wal::WalTableDefinition;
 use influxdb3_wal::{
-    CatalogBatch, CatalogOp, DistinctCacheDefinition, DistinctCacheDelete, LastCacheDefinition,
-    LastCacheDelete, LastCacheSize, Wal, WalConfig, WalFileNotifier, WalOp,
+    CatalogBatch, CatalogOp, DatabaseQuota, DistinctCacheDefinition, DistinctCacheDelete,
+    LastCacheDefinition, LastCacheDelete, LastCacheSize, Wal, WalConfig, WalFileNotifier, WalOp,
 };
 use influxdb3_wal::{CatalogOp::CreateLastCache, DeleteTableDefinition};
 use influxdb3_wal::{DatabaseDefinition, FieldDefinition};
@@ -52,6 +53,7 @@ use observability_deps::tracing::{debug, error, warn};
 use parquet_file::storage::ParquetExecInput;
 use queryable_buffer::QueryableBufferArgs;
 use schema::Schema;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
 use std::{borrow::Borrow, sync::Arc};
 use thiserror::Error;
@@ -122,6 +124,9 @@ pub enum Error {
     #[error("cannot write to a read-only server")]
     NoWriteInReadOnly,
 
+    #[error("quota exceeded: {0}")]
+    QuotaExceeded(#[from] QuotaError),
+
     #[error("error in distinct value cache: {0}")]
     DistinctCacheError(#[from] distinct_cache::ProviderError),
 
@@ -147,6 +152,7 @@ pub struct WriteBufferImpl {
     #[allow(dead_code)]
     parquet_cache: Option<Arc<dyn ParquetCacheOracle>>,
     persisted_files: Arc<PersistedFiles>,
+    quota_tracker: Arc<QuotaTracker>,
     buffer: Arc<QueryableBuffer>,
     wal_config: WalConfig,
     wal: Arc<dyn Wal>,
@@ -155,12 +161,16 @@ pub struct WriteBufferImpl {
     distinct_cache: Arc<DistinctCacheProvider>,
     last_cache: Arc<LastCacheProvider>,
     /// The number of files we will accept for a query
-    query_file_limit: usize,
+    query_file_limit: Arc<AtomicUsize>,
 }
 
 /// The maximum number of snapshots to load on start
 pub const N_SNAPSHOTS_TO_LOAD_ON_START: usize = 1_000;
 
+/// The number of files accepted for a query if no limit is configured, which is about 3 days
+/// worth of files using the default settings
+pub const DEFAULT_QUERY_FILE_LIMIT: usize = 432;
+
 #[derive(Debug)]
 pub struct WriteBufferImplArgs {
     pub persister: Arc<Persister>,
@@ -210,6 +220,9 @@ impl WriteBufferImpl {
             first_snapshot.next_file_id.set_next_id();
         }
 
+        let quota_tracker = Arc::new(QuotaTracker::new_from_persisted_snapshots(
+            &persisted_snapshots,
+        ));
         let persisted_files = Arc::new(PersistedFiles::new_from_persisted_snapshots(
             persisted_snapshots,
         ));
@@ -220,6 +233,7 @@ impl WriteBufferImpl {
             last_cache_provider: Arc::clone(&last_cache),
             distinct_cache_provider: Arc::clone(&distinct_cache),
             persisted_files: Arc::clone(&persisted_files),
+            quota_tracker: Arc::clone(&quota_tracker),
             parquet_cache: parquet_cache.clone(),
         }));
 
@@ -247,9 +261,12 @@ impl WriteBufferImpl {
             distinct_cache,
             last_cache,
             persisted_files,
+            quota_tracker,
             buffer: queryable_buffer,
             metrics: WriteMetrics::new(&metric_registry),
-            query_file_limit: query_file_limit.unwrap_or(432),
+            query_file_limit: Arc::new(AtomicUsize::new(
+                query_file_limit.unwrap_or(DEFAULT_QUERY_FILE_LIMIT),
+            )),
         });
         Ok(result)
     }
@@ -266,6 +283,16 @@ impl WriteBufferImpl {
         Arc::clone(&self.persisted_files)
     }
 
+    pub fn quota_tracker(&self) -> Arc<QuotaTracker> {
+        Arc::clone(&self.quota_tracker)
+    }
+
+    /// The number of files accepted for a query, which can be changed while the server runs and
+    /// applies to queries planned after it is changed
+    pub fn query_file_limit_handle(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.query_file_limit)
+    }
+
     async fn write_lp(
         &self,
         db_name: NamespaceName<'static>,
@@ -287,12 +314,31 @@ impl WriteBufferImpl {
         .v1_parse_lines_and_update_schema(lp, accept_partial, ingest_time, precision)?
         .convert_lines_to_buffer(self.wal_config.gen1_duration);
 
+        let admitted = match self.catalog.db_schema_by_id(&result.valid_data.database_id) {
+            Some(db_schema) => self.quota_tracker.admit_write(
+                &db_schema,
+                &self.persisted_files,
+                &result.valid_data,
+            ),
+            None => Ok(()),
+        };
+
         // if there were catalog updates, ensure they get persisted to the wal, so they're
         // replayed on restart
         let mut ops = Vec::with_capacity(2);
         if let Some(catalog_batch) = result.catalog_updates {
             ops.push(WalOp::Catalog(catalog_batch));
         }
+        if let Err(error) = admitted {
+            // the catalog has already been updated by the validator, so its changes must still
+            // go into the wal even though the write is rejected
+            if !ops.is_empty() {
+                self.wal.write_ops(ops).await?;
+            }
+            self.metrics
+                .record_lines_rejected(&db_name, result.line_count as u64);
+            return Err(error.into());
+        }
         ops.push(WalOp::Write(result.valid_data));
 
         if no_sync {
@@ -343,7 +389,8 @@ impl WriteBufferImpl {
             self.persisted_files
                 .get_files_filtered(db_schema.id, table_def.table_id, filter);
 
-        if parquet_files.len() > self.query_file_limit {
+        let query_file_limit = self.query_file_limit.load(Ordering::Relaxed);
+        if parquet_files.len() > query_file_limit {
             return Err(DataFusionError::External(
                 format!(
                     "Query would exceed file limit of {} parquet files. \
@@ -352,7 +399,7 @@ impl WriteBufferImpl {
                      `--query-file-limit` option in the serve command, however, \
                      query performance will be slower and the server may get \
                      OOM killed or become unstable as a result",
-                    self.query_file_limit
+                    query_file_limit
                 )
                 .into(),
             ));
@@ -515,6 +562,10 @@ impl Bufferer for WriteBufferImpl {
     fn watch_persisted_snapshots(&self) -> Receiver<Option<PersistedSnapshot>> {
         self.buffer.persisted_snapshot_notify_rx()
     }
+
+    fn quota_tracker(&self) -> Arc<QuotaTracker> {
+        self.quota_tracker()
+    }
 }
 
 impl ChunkContainer for WriteBufferImpl {
@@ -890,6 +941,32 @@ impl DatabaseManager for WriteBufferImpl {
         );
         Ok(())
     }
+
+    async fn set_database_quota(
+        &self,
+        db_name: String,
+        quota: DatabaseQuota,
+    ) -> Result<(), self::Error> {
+        let (db_id, db_schema) = self.catalog.db_id_and_schema(&db_name).ok_or_else(|| {
+            self::Error::DatabaseNotFound {
+                db_name: db_name.to_owned(),
+            }
+        })?;
+
+        let catalog_batch = CatalogBatch {
+            time_ns: self.time_provider.now().timestamp_nanos(),
+            database_id: db_id,
+            database_name: Arc::clone(&db_schema.name),
+            ops: vec![CatalogOp::SetDatabaseQuota(quota)],
+        };
+        if let Some(catalog_batch) = self.catalog.apply_catalog_batch(&catalog_batch)? {
+            self.wal
+                .write_ops(vec![WalOp::Catalog(catalog_batch)])
+                .await?;
+        }
+        debug!(db_id = ?db_id, name = ?&db_schema.name, ?quota, "successfully set database quota");
+        Ok(())
+    }
 }
 
 impl WriteBuffer for WriteBufferImpl {}
@@ -1752,6 +1829,8 @@ mod tests {
                     chunk_time: 1,
                     min_time: 0,
                     max_time: 1,
+                    column_encodings: Default::default(),
+                    tag_index: None,
                 },
             );
         }
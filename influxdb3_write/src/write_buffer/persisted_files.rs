@@ -61,10 +61,16 @@ impl PersistedFiles {
             .files
             .get(&db_id)
             .and_then(|tables| tables.get(&table_id))
-            .cloned()
-            .unwrap_or_default()
             .into_iter()
-            .filter(|file| filter.test_time_stamp_min_max(file.min_time, file.max_time))
+            .flatten()
+            .filter(|file| {
+                filter.test_time_stamp_min_max(file.min_time, file.max_time)
+                    && file
+                        .tag_index
+                        .as_ref()
+                        .is_none_or(|index| filter.test_tag_index(index))
+            })
+            .cloned()
             .collect::<Vec<_>>();
 
         files.sort_by(|a, b| b.min_time.cmp(&a.min_time));
@@ -196,8 +202,10 @@ fn update_persisted_files_with_snapshot(
 #[cfg(test)]
 mod tests {
 
+    use arrow::array::{ArrayRef, RecordBatch, StringArray};
     use datafusion::prelude::Expr;
     use datafusion::prelude::col;
+    use datafusion::prelude::lit;
     use datafusion::prelude::lit_timestamp_nano;
     use influxdb3_catalog::catalog::CatalogSequenceNumber;
     use influxdb3_catalog::catalog::TableDefinition;
@@ -205,10 +213,11 @@ mod tests {
     use influxdb3_wal::{SnapshotSequenceNumber, WalFileSequenceNumber};
     use observability_deps::tracing::info;
     use pretty_assertions::assert_eq;
-    use schema::InfluxColumnType;
+    use schema::{InfluxColumnType, InfluxFieldType};
     use std::sync::Arc;
 
     use crate::ParquetFileId;
+    use crate::tag_index::TagIndex;
 
     use super::*;
 
@@ -296,6 +305,8 @@ mod tests {
                     chunk_time,
                     min_time: chunk_time,
                     max_time: chunk_time + 10,
+                    column_encodings: Default::default(),
+                    tag_index: None,
                 }
             })
             .collect();
@@ -398,6 +409,130 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_get_files_with_tag_filters() {
+        let tag_index = |region: &str, hosts: &[&str]| {
+            let batch = RecordBatch::try_from_iter([
+                (
+                    "region",
+                    Arc::new(StringArray::from_iter_values(hosts.iter().map(|_| region)))
+                        as ArrayRef,
+                ),
+                (
+                    "host",
+                    Arc::new(StringArray::from_iter_values(hosts)) as ArrayRef,
+                ),
+            ])
+            .unwrap();
+            TagIndex::build(&[batch], &["region", "host"]).map(Arc::new)
+        };
+        let parquet_files = [
+            ("east", tag_index("east", &["a", "b"])),
+            ("west", tag_index("west", &["c"])),
+            // files persisted without an index can not be pruned:
+            ("unindexed", None),
+        ]
+        .into_iter()
+        .map(|(name, tag_index)| ParquetFile {
+            id: ParquetFileId::new(),
+            path: format!("/path/{name}.parquet"),
+            size_bytes: 1,
+            row_count: 1,
+            chunk_time: 0,
+            min_time: 0,
+            max_time: 10,
+            column_encodings: Default::default(),
+            tag_index,
+        })
+        .collect();
+        let persisted_snapshots = vec![build_snapshot(parquet_files, 0, 0, 0)];
+        let persisted_files = PersistedFiles::new_from_persisted_snapshots(persisted_snapshots);
+
+        struct TestCase<'a> {
+            filter: &'a [Expr],
+            expected_files: &'a [&'a str],
+        }
+
+        let test_cases = [
+            TestCase {
+                filter: &[],
+                expected_files: &["east", "west", "unindexed"],
+            },
+            TestCase {
+                filter: &[col("region").eq(lit("east"))],
+                expected_files: &["east", "unindexed"],
+            },
+            TestCase {
+                filter: &[col("region").in_list(vec![lit("east"), lit("west")], false)],
+                expected_files: &["east", "west", "unindexed"],
+            },
+            TestCase {
+                filter: &[col("region").eq(lit("west")), col("host").eq(lit("a"))],
+                expected_files: &["unindexed"],
+            },
+            TestCase {
+                filter: &[col("region").eq(lit("west")).and(col("host").eq(lit("c")))],
+                expected_files: &["west", "unindexed"],
+            },
+            TestCase {
+                filter: &[col("host").eq(lit("z"))],
+                expected_files: &["unindexed"],
+            },
+            // only predicates that restrict a tag to a set of values are used:
+            TestCase {
+                filter: &[col("region").not_eq(lit("east"))],
+                expected_files: &["east", "west", "unindexed"],
+            },
+            TestCase {
+                filter: &[col("usage").eq(lit(0.5))],
+                expected_files: &["east", "west", "unindexed"],
+            },
+        ];
+
+        let table_def = Arc::new(
+            TableDefinition::new(
+                TableId::from(0),
+                "test-tbl".into(),
+                vec![
+                    (ColumnId::from(0), "region".into(), InfluxColumnType::Tag),
+                    (ColumnId::from(1), "host".into(), InfluxColumnType::Tag),
+                    (
+                        ColumnId::from(2),
+                        "usage".into(),
+                        InfluxColumnType::Field(InfluxFieldType::Float),
+                    ),
+                    (
+                        ColumnId::from(3),
+                        "time".into(),
+                        InfluxColumnType::Timestamp,
+                    ),
+                ],
+                vec![ColumnId::from(0), ColumnId::from(1)],
+            )
+            .unwrap(),
+        );
+
+        for t in test_cases {
+            let filter = ChunkFilter::new(&table_def, t.filter).unwrap();
+            let mut filtered_files = persisted_files
+                .get_files_filtered(DbId::from(0), TableId::from(0), &filter)
+                .into_iter()
+                .map(|f| f.path)
+                .collect::<Vec<_>>();
+            filtered_files.sort();
+            let mut expected_files = t
+                .expected_files
+                .iter()
+                .map(|name| format!("/path/{name}.parquet"))
+                .collect::<Vec<_>>();
+            expected_files.sort();
+            assert_eq!(
+                expected_files, filtered_files,
+                "wrong filtered files for filter: {filter:?}"
+            );
+        }
+    }
+
     fn build_persisted_snapshots() -> Vec<PersistedSnapshot> {
         let mut all_persisted_snapshot_files = Vec::new();
         let parquet_files_1 = build_parquet_files(5);
@@ -439,6 +574,8 @@ mod tests {
                 chunk_time: 10,
                 min_time: 10,
                 max_time: 200,
+                column_encodings: Default::default(),
+                tag_index: None,
             })
             .collect();
         parquet_files
@@ -1,6 +1,9 @@
 use crate::chunk::BufferChunk;
+use crate::encoding::ColumnEncodings;
 use crate::paths::ParquetFilePath;
 use crate::persister::Persister;
+use crate::quota::QuotaTracker;
+use crate::tag_index::TagIndex;
 use crate::write_buffer::persisted_files::PersistedFiles;
 use crate::write_buffer::table_buffer::TableBuffer;
 use crate::{ChunkFilter, ParquetFile, ParquetFileId, PersistedSnapshot};
@@ -25,7 +28,7 @@ use iox_query::chunk_statistics::{NoColumnRanges, create_chunk_statistics};
 use iox_query::exec::Executor;
 use iox_query::frontend::reorg::ReorgPlanner;
 use object_store::path::Path;
-use observability_deps::tracing::{error, info};
+use observability_deps::tracing::{debug, error, info};
 use parking_lot::Mutex;
 use parking_lot::RwLock;
 use parquet::format::FileMetaData;
@@ -45,6 +48,7 @@ pub struct QueryableBuffer {
     last_cache_provider: Arc<LastCacheProvider>,
     persister: Arc<Persister>,
     persisted_files: Arc<PersistedFiles>,
+    quota_tracker: Arc<QuotaTracker>,
     buffer: Arc<RwLock<BufferState>>,
     parquet_cache: Option<Arc<dyn ParquetCacheOracle>>,
     /// Sends a notification to this watch channel whenever a snapshot info is persisted
@@ -60,6 +64,7 @@ pub struct QueryableBufferArgs {
     pub last_cache_provider: Arc<LastCacheProvider>,
     pub distinct_cache_provider: Arc<DistinctCacheProvider>,
     pub persisted_files: Arc<PersistedFiles>,
+    pub quota_tracker: Arc<QuotaTracker>,
     pub parquet_cache: Option<Arc<dyn ParquetCacheOracle>>,
 }
 
@@ -72,6 +77,7 @@ impl QueryableBuffer {
             last_cache_provider,
             distinct_cache_provider,
             persisted_files,
+            quota_tracker,
             parquet_cache,
         }: QueryableBufferArgs,
     ) -> Self {
@@ -85,6 +91,7 @@ impl QueryableBuffer {
             distinct_cache_provider,
             persister,
             persisted_files,
+            quota_tracker,
             buffer,
             parquet_cache,
             persisted_snapshot_notify_rx,
@@ -153,6 +160,7 @@ impl QueryableBuffer {
     /// last cache so the data is queryable.
     fn buffer_contents(&self, write: Arc<WalContents>) {
         self.write_wal_contents_to_caches(&write);
+        self.quota_tracker.observe_wal_contents(&write);
         let mut buffer = self.buffer.write();
         buffer.buffer_ops(
             &write.ops,
@@ -173,6 +181,7 @@ impl QueryableBuffer {
             "Buffering contents and persisting snapshotted data"
         );
         self.write_wal_contents_to_caches(&write);
+        self.quota_tracker.observe_wal_contents(&write);
 
         let persist_jobs = {
             let mut buffer = self.buffer.write();
@@ -230,6 +239,7 @@ impl QueryableBuffer {
         let persister = Arc::clone(&self.persister);
         let executor = Arc::clone(&self.executor);
         let persisted_files = Arc::clone(&self.persisted_files);
+        let quota_tracker = Arc::clone(&self.quota_tracker);
         let wal_file_number = write.wal_file_number;
         let buffer = Arc::clone(&self.buffer);
         let catalog = Arc::clone(&self.catalog);
@@ -286,6 +296,7 @@ impl QueryableBuffer {
                 let parquet_cache = parquet_cache.clone();
                 let buffer = Arc::clone(&buffer);
                 let persisted_files = Arc::clone(&persisted_files);
+                let quota_tracker = Arc::clone(&quota_tracker);
 
                 set.spawn(async move {
                     let path = persist_job.path.to_string();
@@ -298,6 +309,8 @@ impl QueryableBuffer {
                     let SortDedupePersistSummary {
                         file_size_bytes,
                         file_meta_data,
+                        column_encodings,
+                        tag_index,
                     } = sort_dedupe_persist(
                         persist_job,
                         persister,
@@ -324,6 +337,8 @@ impl QueryableBuffer {
                         chunk_time,
                         min_time,
                         max_time,
+                        column_encodings,
+                        tag_index: tag_index.map(Arc::new),
                     };
 
                     {
@@ -332,6 +347,7 @@ impl QueryableBuffer {
 
                         // add file first
                         persisted_files.add_persisted_file(&database_id, &table_id, &parquet_file);
+                        quota_tracker.observe_persisted_file(database_id, &parquet_file);
                         // then clear the buffer
                         if let Some(db) = buffer.db_to_table.get_mut(&database_id) {
                             if let Some(table) = db.get_mut(&table_id) {
@@ -564,6 +580,12 @@ impl BufferState {
                             CatalogOp::DeleteTrigger(_) => {}
                             CatalogOp::EnableTrigger(_) => {}
                             CatalogOp::DisableTrigger(_) => {}
+                            CatalogOp::CreateDownsamplingTask(_) => {}
+                            CatalogOp::DeleteDownsamplingTask(_) => {}
+                            CatalogOp::EnableDownsamplingTask(_) => {}
+                            CatalogOp::DisableDownsamplingTask(_) => {}
+                            CatalogOp::AdvanceDownsamplingTask(_) => {}
+                            CatalogOp::SetDatabaseQuota(_) => {}
                         }
                     }
                 }
@@ -625,13 +647,22 @@ struct PersistJob {
 pub(crate) struct SortDedupePersistSummary {
     pub file_size_bytes: u64,
     pub file_meta_data: FileMetaData,
+    pub column_encodings: ColumnEncodings,
+    pub tag_index: Option<TagIndex>,
 }
 
 impl SortDedupePersistSummary {
-    fn new(file_size_bytes: u64, file_meta_data: FileMetaData) -> Self {
+    fn new(
+        file_size_bytes: u64,
+        file_meta_data: FileMetaData,
+        column_encodings: ColumnEncodings,
+        tag_index: Option<TagIndex>,
+    ) -> Self {
         Self {
             file_size_bytes,
             file_meta_data,
+            column_encodings,
+            tag_index,
         }
     }
 }
@@ -701,13 +732,30 @@ async fn sort_dedupe_persist(
         .await
         .context("failed to execute the sort and deduplication of chunked data from the buffer")?;
 
+    // Sample the sorted data to choose an encoding for each column:
+    let column_encodings = ColumnEncodings::analyze(&data);
+    debug!(
+        path = %persist_job.path.to_string(),
+        ?column_encodings,
+        "chose column encodings for parquet file"
+    );
+
+    // Index the series that have each tag value, so queries can skip the file when it has none
+    // of the series they select:
+    let tag_index = TagIndex::build(&data, &persist_job.schema.series_key().unwrap_or_default());
+    debug!(
+        path = %persist_job.path.to_string(),
+        series_count = ?tag_index.as_ref().map(|i| i.series_count()),
+        "built tag index for parquet file"
+    );
+
     // keep attempting to persist forever. If we can't reach the object store, we'll stop accepting
     // writes elsewhere in the system, so we need to keep trying to persist.
     loop {
         let batch_stream = stream_from_batches(persist_job.schema.as_arrow(), data.clone());
 
         match persister
-            .persist_parquet_file(persist_job.path.clone(), batch_stream)
+            .persist_parquet_file(persist_job.path.clone(), batch_stream, &column_encodings)
             .await
         {
             Ok((size_bytes, parquet_meta, to_cache)) => {
@@ -719,7 +767,12 @@ async fn sort_dedupe_persist(
                     );
                     parquet_cache_oracle.register(cache_request);
                 }
-                return Ok(SortDedupePersistSummary::new(size_bytes, parquet_meta));
+                return Ok(SortDedupePersistSummary::new(
+                    size_bytes,
+                    parquet_meta,
+                    column_encodings,
+                    tag_index,
+                ));
             }
             Err(e) => {
                 error!(
@@ -792,6 +845,7 @@ mod tests {
             )
             .unwrap(),
             persisted_files: Arc::new(PersistedFiles::new()),
+            quota_tracker: Arc::new(QuotaTracker::new()),
             parquet_cache: None,
         };
         let queryable_buffer = QueryableBuffer::new(queryable_buffer_args);